@@ -0,0 +1,7 @@
+// the assets are built into the binary with `include_dir!`, which does not
+// tell cargo to build again when one of them changes
+fn main() {
+    for dir in ["resources/textures", "resources/fonts", "resources/audio", "levels", "src/shaders"] {
+        println!("cargo:rerun-if-changed={}", dir);
+    }
+}
@@ -0,0 +1,263 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
+use std::time::SystemTime;
+use include_dir::{include_dir, Dir};
+
+use crate::error::ResourceError;
+
+// folders built into the binary, with the logical path their files are
+// found under, only the folders the game loads from
+static EMBEDDED : [(&str, Dir); 5] = [
+    ("resources/textures", include_dir!("$CARGO_MANIFEST_DIR/resources/textures")),
+    ("resources/fonts", include_dir!("$CARGO_MANIFEST_DIR/resources/fonts")),
+    ("resources/audio", include_dir!("$CARGO_MANIFEST_DIR/resources/audio")),
+    ("levels", include_dir!("$CARGO_MANIFEST_DIR/levels")),
+    ("src/shaders", include_dir!("$CARGO_MANIFEST_DIR/src/shaders")),
+];
+
+/// Assets read in place of the built in ones
+#[derive(Debug)]
+enum Override {
+    Directory(PathBuf),
+    /// the files of a zip archive, read when it is opened
+    Archive(HashMap<String, Vec<u8>>),
+}
+
+/// The files the game is made of, found by a logical path such as
+/// `resources/textures/paddle.png` with forward slashes on every platform.
+///
+/// All assets are built into the binary, so the game runs from any
+/// directory. An override directory or zip archive can replace any of them
+/// by holding a file under the same logical path.
+#[derive(Debug, Default)]
+pub struct Assets {
+    overrides : Option<Override>,
+}
+
+impl Assets {
+    /// only the assets built into the binary
+    pub fn embedded() -> Self {
+        Self::default()
+    }
+
+    /// the files in the directory or zip archive at `path` take the place of
+    /// the built in assets with the same logical path
+    pub fn with_override(path : &Path) -> Result<Self, ResourceError> {
+        let overrides = if path.is_dir() {
+            Override::Directory(path.into())
+        } else {
+            Override::Archive(read_archive(path)?)
+        };
+        Ok(Self {
+            overrides : Some(overrides),
+        })
+    }
+
+    /// the content of an asset
+    pub fn read(&self, path : &str) -> Result<Cow<'_, [u8]>, ResourceError> {
+        match &self.overrides {
+            Some(Override::Directory(dir)) => {
+                if let Some(file) = disk_path(dir, path).filter(|file| file.is_file()) {
+                    return std::fs::read(&file)
+                        .map(Cow::Owned)
+                        .map_err(|source| ResourceError::Io { path : file.display().to_string(), source });
+                }
+            },
+            Some(Override::Archive(files)) => {
+                if let Some(data) = files.get(path) {
+                    return Ok(Cow::Borrowed(data));
+                }
+            },
+            None => {},
+        }
+        embedded(path).map(Cow::Borrowed).ok_or_else(|| ResourceError::MissingAsset(path.into()))
+    }
+
+    /// the content of a text asset
+    pub fn read_to_string(&self, path : &str) -> Result<String, ResourceError> {
+        String::from_utf8(self.read(path)?.into_owned()).map_err(|err| ResourceError::Io {
+            path : path.into(),
+            source : std::io::Error::new(std::io::ErrorKind::InvalidData, err),
+        })
+    }
+
+    /// when the file of the asset in the override directory last changed,
+    /// `None` for assets that can not change while the game runs
+    pub fn modified(&self, path : &str) -> Option<SystemTime> {
+        match &self.overrides {
+            Some(Override::Directory(dir)) => disk_path(dir, path)
+                .and_then(|file| std::fs::metadata(file).and_then(|metadata| metadata.modified()).ok()),
+            _ => None,
+        }
+    }
+}
+
+/// a built in asset
+fn embedded(path : &str) -> Option<&'static [u8]> {
+    EMBEDDED.iter().find_map(|(prefix, dir)| {
+        let relative = path.strip_prefix(prefix)?.strip_prefix('/')?;
+        dir.get_file(relative).map(|file| file.contents())
+    })
+}
+
+/// the file of a logical path in `dir`, `None` for a path that would lead
+/// outside of it
+fn disk_path(dir : &Path, path : &str) -> Option<PathBuf> {
+    let relative = Path::new(path);
+    relative.components()
+        .all(|component| matches!(component, Component::Normal(_)))
+        .then(|| dir.join(relative))
+}
+
+/// read all files of a zip archive, their names are the logical paths.
+/// Backslashes in names count as separators, and when everything is in a
+/// single folder that is not one of the asset folders, like zip tools make
+/// when packing a directory, that folder is left out.
+fn read_archive(path : &Path) -> Result<HashMap<String, Vec<u8>>, ResourceError> {
+    let archive_error = |message : String| ResourceError::Archive { path : path.display().to_string(), message };
+
+    let file = std::fs::File::open(path)
+        .map_err(|source| ResourceError::Io { path : path.display().to_string(), source })?;
+    let mut archive = zip::ZipArchive::new(std::io::BufReader::new(file))
+        .map_err(|err| archive_error(err.to_string()))?;
+
+    let mut files = HashMap::new();
+    for index in 0..archive.len() {
+        let mut entry = archive.by_index(index).map_err(|err| archive_error(err.to_string()))?;
+        if !entry.is_file() {
+            continue;
+        }
+        let name = entry.name().replace('\\', "/");
+        let mut data = Vec::with_capacity(entry.size() as usize);
+        entry.read_to_end(&mut data).map_err(|err| archive_error(format!("{}: {}", name, err)))?;
+        files.insert(name, data);
+    }
+    Ok(strip_common_folder(files))
+}
+
+/// the files without the folder all of them are in, if there is one and it
+/// does not hold the built in assets itself
+fn strip_common_folder(files : HashMap<String, Vec<u8>>) -> HashMap<String, Vec<u8>> {
+    let folder = |name : &str| name.split_once('/').map(|(folder, _)| folder.to_string());
+    let common = match files.keys().next().and_then(|name| folder(name)) {
+        Some(common) if files.keys().all(|name| folder(name).as_ref() == Some(&common)) => common,
+        _ => return files,
+    };
+    let is_asset_folder = EMBEDDED.iter()
+        .any(|(prefix, _)| prefix.split('/').next() == Some(common.as_str()));
+    if is_asset_folder {
+        return files;
+    }
+    files.into_iter()
+        .map(|(name, data)| (name[common.len() + 1..].to_string(), data))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use crate::test_util::TempDir;
+
+    const LEVEL : &str = "levels/one.lvl";
+
+    /// a zip archive in `dir` holding `files`
+    fn zip(dir : &TempDir, files : &[(&str, &str)]) -> PathBuf {
+        let path = dir.path().join("assets.zip");
+        let mut writer = zip::ZipWriter::new(std::fs::File::create(&path).unwrap());
+        for (name, content) in files {
+            writer.start_file(*name, zip::write::FileOptions::default()).unwrap();
+            writer.write_all(content.as_bytes()).unwrap();
+        }
+        writer.finish().unwrap();
+        path
+    }
+
+    fn built_in(path : &str) -> String {
+        String::from_utf8(embedded(path).unwrap().to_vec()).unwrap()
+    }
+
+    #[test]
+    fn only_the_loaded_folders_are_built_in() {
+        for path in ["resources/textures/block.png", "resources/fonts/DejaVuSansMono-Bold.ttf",
+                "resources/audio/bleep.wav", LEVEL, "src/shaders/sprite.vs"] {
+            assert!(embedded(path).is_some(), "{}", path);
+        }
+        assert!(embedded("resources/objects/cube/cube.obj").is_none());
+    }
+
+    #[test]
+    fn override_directory_comes_first() {
+        let dir = TempDir::new("assets_directory");
+        std::fs::create_dir_all(dir.path().join("levels")).unwrap();
+        std::fs::write(dir.path().join(LEVEL), "5 5").unwrap();
+
+        let assets = Assets::with_override(dir.path()).unwrap();
+        assert_eq!(assets.read_to_string(LEVEL).unwrap(), "5 5");
+        assert!(assets.modified(LEVEL).is_some());
+        // files missing from the override are the built in ones
+        assert_eq!(assets.read_to_string("levels/two.lvl").unwrap(), built_in("levels/two.lvl"));
+        assert!(assets.modified("levels/two.lvl").is_none());
+        assert!(matches!(assets.read("levels/none.lvl"), Err(ResourceError::MissingAsset(_))));
+    }
+
+    #[test]
+    fn override_archive_comes_first() {
+        let dir = TempDir::new("assets_archive");
+        let path = zip(&dir, &[(LEVEL, "5 5")]);
+
+        let assets = Assets::with_override(&path).unwrap();
+        assert_eq!(assets.read_to_string(LEVEL).unwrap(), "5 5");
+        assert_eq!(assets.read_to_string("levels/two.lvl").unwrap(), built_in("levels/two.lvl"));
+        assert!(assets.modified(LEVEL).is_none());
+    }
+
+    #[test]
+    fn disk_paths_stay_inside_the_directory() {
+        let dir = Path::new("overrides");
+        assert_eq!(disk_path(dir, LEVEL), Some(dir.join(LEVEL)));
+        assert_eq!(disk_path(dir, "../secret.txt"), None);
+        assert_eq!(disk_path(dir, "levels/../../secret.txt"), None);
+        assert_eq!(disk_path(dir, "/etc/passwd"), None);
+        assert_eq!(disk_path(dir, "./levels/one.lvl"), None);
+    }
+
+    #[test]
+    fn archive_names_are_logical_paths() {
+        let dir = TempDir::new("archive_names");
+        let path = zip(&dir, &[
+            ("levels/one.lvl", "1"),
+            ("levels\\two.lvl", "2"),
+            ("resources/textures/ball.png", "3"),
+        ]);
+        let mut names : Vec<String> = read_archive(&path).unwrap().into_keys().collect();
+        names.sort();
+        assert_eq!(names, ["levels/one.lvl", "levels/two.lvl", "resources/textures/ball.png"]);
+    }
+
+    #[test]
+    fn archive_folder_around_everything_is_left_out() {
+        let dir = TempDir::new("archive_folder");
+        let path = zip(&dir, &[
+            ("mod/levels/one.lvl", "1"),
+            ("mod\\resources\\textures\\ball.png", "2"),
+        ]);
+        let files = read_archive(&path).unwrap();
+        assert_eq!(files.get("levels/one.lvl").map(Vec::as_slice), Some(&b"1"[..]));
+        assert_eq!(files.get("resources/textures/ball.png").map(Vec::as_slice), Some(&b"2"[..]));
+
+        // an asset folder is part of the logical paths
+        let path = zip(&dir, &[("levels/one.lvl", "1"), ("levels/two.lvl", "2")]);
+        assert!(read_archive(&path).unwrap().contains_key("levels/one.lvl"));
+    }
+
+    #[test]
+    fn broken_archive_is_an_error() {
+        let dir = TempDir::new("archive_broken");
+        let path = dir.path().join("assets.zip");
+        std::fs::write(&path, "not a zip").unwrap();
+        assert!(matches!(read_archive(&path), Err(ResourceError::Archive { .. })));
+    }
+}
@@ -0,0 +1,367 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::assets::Assets;
+use crate::error::ResourceError;
+use crate::game::GameEvent;
+
+// background music, played in a loop
+const MUSIC_FILE : &str = "resources/audio/music.wav";
+
+/// Sound effects of the game
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Sound {
+    BrickHit,
+    SolidHit,
+    PaddleHit,
+    PowerUp,
+}
+
+impl Sound {
+    pub const ALL : [Sound; 4] = [
+        Sound::BrickHit,
+        Sound::SolidHit,
+        Sound::PaddleHit,
+        Sound::PowerUp,
+    ];
+
+    pub fn file(&self) -> &'static str {
+        match self {
+            Sound::BrickHit => "resources/audio/bleep.wav",
+            Sound::SolidHit => "resources/audio/solid.wav",
+            Sound::PaddleHit => "resources/audio/paddle.wav",
+            Sound::PowerUp => "resources/audio/powerup.wav",
+        }
+    }
+}
+
+/// Something that plays sound, volumes go from 0 to 1
+pub trait AudioBackend {
+    /// decode a sound effect so it can be played without delay, `path` is
+    /// the asset `data` was read from
+    fn load_sound(&mut self, sound : Sound, path : &str, data : &[u8]) -> Result<(), ResourceError>;
+    /// start a sound effect, several can play at the same time
+    fn play_sound(&mut self, sound : Sound, volume : f32);
+    /// loop the music, replacing any music that is playing
+    fn play_music(&mut self, path : &str, data : &[u8], volume : f32) -> Result<(), ResourceError>;
+    fn set_music_volume(&mut self, volume : f32);
+    fn stop_music(&mut self);
+}
+
+/// Backend that plays nothing, for when there is no audio output
+#[derive(Debug, Default)]
+pub struct NullBackend;
+
+impl AudioBackend for NullBackend {
+    fn load_sound(&mut self, _sound : Sound, _path : &str, _data : &[u8]) -> Result<(), ResourceError> {
+        Ok(())
+    }
+
+    fn play_sound(&mut self, _sound : Sound, _volume : f32) {}
+
+    fn play_music(&mut self, _path : &str, _data : &[u8], _volume : f32) -> Result<(), ResourceError> {
+        Ok(())
+    }
+
+    fn set_music_volume(&mut self, _volume : f32) {}
+
+    fn stop_music(&mut self) {}
+}
+
+/// A call made on a `RecordingBackend`
+#[derive(Debug, Clone, PartialEq)]
+pub enum AudioCommand {
+    LoadSound(Sound),
+    PlaySound { sound : Sound, volume : f32 },
+    PlayMusic { path : String, volume : f32 },
+    SetMusicVolume(f32),
+    StopMusic,
+}
+
+/// Backend that only writes down what it was asked to play, so the sound of
+/// a game can be checked without an audio device
+#[derive(Debug, Default)]
+pub struct RecordingBackend {
+    commands : Rc<RefCell<Vec<AudioCommand>>>,
+}
+
+impl RecordingBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// the recorded commands, shared so they can be read after the backend
+    /// is handed to `Audio`
+    pub fn commands(&self) -> Rc<RefCell<Vec<AudioCommand>>> {
+        self.commands.clone()
+    }
+}
+
+impl AudioBackend for RecordingBackend {
+    fn load_sound(&mut self, sound : Sound, _path : &str, _data : &[u8]) -> Result<(), ResourceError> {
+        self.commands.borrow_mut().push(AudioCommand::LoadSound(sound));
+        Ok(())
+    }
+
+    fn play_sound(&mut self, sound : Sound, volume : f32) {
+        self.commands.borrow_mut().push(AudioCommand::PlaySound { sound, volume });
+    }
+
+    fn play_music(&mut self, path : &str, _data : &[u8], volume : f32) -> Result<(), ResourceError> {
+        self.commands.borrow_mut().push(AudioCommand::PlayMusic { path : path.into(), volume });
+        Ok(())
+    }
+
+    fn set_music_volume(&mut self, volume : f32) {
+        self.commands.borrow_mut().push(AudioCommand::SetMusicVolume(volume));
+    }
+
+    fn stop_music(&mut self) {
+        self.commands.borrow_mut().push(AudioCommand::StopMusic);
+    }
+}
+
+/// Plays through the default output device, needs the `audio` feature
+#[cfg(feature = "audio")]
+pub struct RodioBackend {
+    // the output stops when the stream is dropped
+    _stream : rodio::OutputStream,
+    handle : rodio::OutputStreamHandle,
+    sounds : std::collections::HashMap<Sound, std::sync::Arc<[u8]>>,
+    music : Option<rodio::Sink>,
+}
+
+#[cfg(feature = "audio")]
+impl RodioBackend {
+    pub fn new() -> Result<Self, ResourceError> {
+        let (stream, handle) = rodio::OutputStream::try_default()
+            .map_err(|err| ResourceError::AudioDevice(err.to_string()))?;
+        Ok(Self {
+            _stream : stream,
+            handle,
+            sounds : std::collections::HashMap::new(),
+            music : None,
+        })
+    }
+
+    /// keep a sound file after checking that it can be decoded
+    fn check_sound(path : &str, data : &[u8]) -> Result<std::sync::Arc<[u8]>, ResourceError> {
+        let data : std::sync::Arc<[u8]> = data.into();
+        rodio::Decoder::new(std::io::Cursor::new(data.clone()))
+            .map_err(|err| ResourceError::Audio { path : path.into(), message : err.to_string() })?;
+        Ok(data)
+    }
+}
+
+#[cfg(feature = "audio")]
+impl AudioBackend for RodioBackend {
+    fn load_sound(&mut self, sound : Sound, path : &str, data : &[u8]) -> Result<(), ResourceError> {
+        let data = Self::check_sound(path, data)?;
+        self.sounds.insert(sound, data);
+        Ok(())
+    }
+
+    fn play_sound(&mut self, sound : Sound, volume : f32) {
+        use rodio::Source;
+
+        if let Some(data) = self.sounds.get(&sound) {
+            if let Ok(source) = rodio::Decoder::new(std::io::Cursor::new(data.clone())) {
+                if let Err(err) = self.handle.play_raw(source.convert_samples().amplify(volume)) {
+                    eprintln!("Error: cannot play {:?}: {}", sound, err);
+                }
+            }
+        }
+    }
+
+    fn play_music(&mut self, path : &str, data : &[u8], volume : f32) -> Result<(), ResourceError> {
+        use rodio::Source;
+
+        let data = Self::check_sound(path, data)?;
+        let source = rodio::Decoder::new(std::io::Cursor::new(data))
+            .map_err(|err| ResourceError::Audio { path : path.into(), message : err.to_string() })?;
+        let sink = rodio::Sink::try_new(&self.handle)
+            .map_err(|err| ResourceError::AudioDevice(err.to_string()))?;
+        sink.set_volume(volume);
+        sink.append(source.repeat_infinite());
+        self.stop_music();
+        self.music = Some(sink);
+        Ok(())
+    }
+
+    fn set_music_volume(&mut self, volume : f32) {
+        if let Some(sink) = &self.music {
+            sink.set_volume(volume);
+        }
+    }
+
+    fn stop_music(&mut self) {
+        if let Some(sink) = self.music.take() {
+            sink.stop();
+        }
+    }
+}
+
+/// Volume of the sound channels, the master volume applies to both others
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Mixer {
+    pub master : f32,
+    pub sfx : f32,
+    pub music : f32,
+}
+
+impl Default for Mixer {
+    fn default() -> Self {
+        Self {
+            master : 1.0,
+            sfx : 0.8,
+            music : 0.5,
+        }
+    }
+}
+
+impl Mixer {
+    /// volume a sound effect is played with
+    pub fn sfx_volume(&self) -> f32 {
+        (self.master * self.sfx).clamp(0.0, 1.0)
+    }
+
+    /// volume the music is played with
+    pub fn music_volume(&self) -> f32 {
+        (self.master * self.music).clamp(0.0, 1.0)
+    }
+}
+
+/// Turns game events into sound, through any `AudioBackend`
+pub struct Audio {
+    backend : Box<dyn AudioBackend>,
+    mixer : Mixer,
+}
+
+impl Audio {
+    pub fn new(backend : Box<dyn AudioBackend>) -> Self {
+        Self {
+            backend,
+            mixer : Mixer::default(),
+        }
+    }
+
+    /// the audio output of this machine, or silence when there is none
+    pub fn default_backend() -> Box<dyn AudioBackend> {
+        #[cfg(feature = "audio")]
+        {
+            match RodioBackend::new() {
+                Ok(backend) => return Box::new(backend),
+                Err(err) => eprintln!("Error: {:#}", anyhow::Error::new(err)),
+            }
+        }
+        Box::new(NullBackend)
+    }
+
+    /// load all sound effects
+    pub fn load(&mut self, assets : &Assets) -> Result<(), ResourceError> {
+        for sound in Sound::ALL {
+            self.backend.load_sound(sound, sound.file(), &assets.read(sound.file())?)?;
+        }
+        Ok(())
+    }
+
+    /// start looping the background music
+    pub fn start_music(&mut self, assets : &Assets) -> Result<(), ResourceError> {
+        self.backend.play_music(MUSIC_FILE, &assets.read(MUSIC_FILE)?, self.mixer.music_volume())
+    }
+
+    pub fn stop_music(&mut self) {
+        self.backend.stop_music();
+    }
+
+    pub fn mixer(&self) -> &Mixer {
+        &self.mixer
+    }
+
+    /// change the channel volumes, the music follows right away
+    pub fn set_mixer(&mut self, mixer : Mixer) {
+        self.mixer = Mixer {
+            master : mixer.master.clamp(0.0, 1.0),
+            sfx : mixer.sfx.clamp(0.0, 1.0),
+            music : mixer.music.clamp(0.0, 1.0),
+        };
+        self.backend.set_music_volume(self.mixer.music_volume());
+    }
+
+    pub fn play(&mut self, sound : Sound) {
+        self.backend.play_sound(sound, self.mixer.sfx_volume());
+    }
+
+    /// play the sound that goes with a game event, if any. Losing a ball and
+    /// changing screens stay silent, the music keeps playing through them
+    /// and there is no sound for them among the original assets.
+    pub fn handle_event(&mut self, event : &GameEvent) {
+        match event {
+            GameEvent::BrickHit { solid : true, .. } => self.play(Sound::SolidHit),
+            GameEvent::BrickHit { solid : false, .. } => self.play(Sound::BrickHit),
+            GameEvent::PaddleHit => self.play(Sound::PaddleHit),
+            GameEvent::PowerUpCollected(_) => self.play(Sound::PowerUp),
+            GameEvent::BallLost | GameEvent::StateChanged(_) => {},
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::GameState;
+    use crate::power_up::PowerUpKind;
+
+    fn recording_audio() -> (Audio, Rc<RefCell<Vec<AudioCommand>>>) {
+        let backend = RecordingBackend::new();
+        let commands = backend.commands();
+        (Audio::new(Box::new(backend)), commands)
+    }
+
+    fn play(sound : Sound, volume : f32) -> AudioCommand {
+        AudioCommand::PlaySound { sound, volume }
+    }
+
+    #[test]
+    fn events_play_their_sound() {
+        let (mut audio, commands) = recording_audio();
+        let volume = Mixer::default().sfx_volume();
+        audio.handle_event(&GameEvent::BrickHit { solid : false, destroyed : true });
+        audio.handle_event(&GameEvent::BrickHit { solid : true, destroyed : false });
+        audio.handle_event(&GameEvent::PaddleHit);
+        audio.handle_event(&GameEvent::PowerUpCollected(PowerUpKind::Speed));
+        audio.handle_event(&GameEvent::BallLost);
+        audio.handle_event(&GameEvent::StateChanged(GameState::GameOver));
+        assert_eq!(*commands.borrow(), vec![
+            play(Sound::BrickHit, volume),
+            play(Sound::SolidHit, volume),
+            play(Sound::PaddleHit, volume),
+            play(Sound::PowerUp, volume),
+        ]);
+    }
+
+    #[test]
+    fn mixer_scales_the_volumes() {
+        let (mut audio, commands) = recording_audio();
+        audio.set_mixer(Mixer { master : 0.5, sfx : 0.5, music : 0.8 });
+        audio.handle_event(&GameEvent::PaddleHit);
+        audio.start_music(&Assets::embedded()).unwrap();
+        audio.set_mixer(Mixer { master : 2.0, sfx : -1.0, music : 0.25 });
+        audio.handle_event(&GameEvent::PaddleHit);
+        assert_eq!(*commands.borrow(), vec![
+            AudioCommand::SetMusicVolume(0.4),
+            play(Sound::PaddleHit, 0.25),
+            AudioCommand::PlayMusic { path : MUSIC_FILE.into(), volume : 0.4 },
+            AudioCommand::SetMusicVolume(0.25),
+            play(Sound::PaddleHit, 0.0),
+        ]);
+    }
+
+    #[test]
+    fn load_reads_every_sound() {
+        let (mut audio, commands) = recording_audio();
+        audio.load(&Assets::embedded()).unwrap();
+        let loaded : Vec<AudioCommand> = Sound::ALL.iter().map(|sound| AudioCommand::LoadSound(*sound)).collect();
+        assert_eq!(*commands.borrow(), loaded);
+    }
+}
@@ -1,92 +1,384 @@
-
-use std::rc::Rc;
-use crate::texture::Texture;
-use crate::game_object::GameObject;
-use crate::sprite_renderer::SpriteRenderer;
-
-
-#[derive(Debug)]
-pub struct BallObject {
-
-    game_object: GameObject,
-    radius: f32,
-    stuck : bool,
-}
-
-impl BallObject {
-
-    pub fn new( 
-        position : glm::Vec2,
-        radius   : f32,
-        velocity : glm::Vec2,
-        texture : Rc<Texture> ) -> Self {
-
-        Self {
-            game_object: GameObject::new(
-                position,
-                glm::vec2(radius * 2.0, radius * 2.0),
-                velocity,
-                glm::vec3(1.0, 1.0, 1.0),
-                0.0,
-                texture,
-                false,
-            ),
-            radius,
-            stuck : true,
-        }
-    }
-
-    pub fn draw(&self, renderer : &SpriteRenderer ) {
-        self.game_object.draw(renderer);
-    }
-
-    pub fn un_stuck(&mut self) {
-        self.stuck = false;
-    }
- 
-    pub fn do_move( &mut self, dt : f32, window_width:u32) -> glm::Vec2 {
-        let mut position = self.game_object.get_position();
-        let size_x = self.game_object.get_size().x;
-
-        if !self.stuck
-        {
-            let mut velocity = self.game_object.get_velocity();
-
-            // move the ball
-            position = position + velocity.scale(dt);
-
-            // check if outside window bounds; if so, reverse velocity
-            // and restore at correct position
-            if position.x <= 0.0
-            {
-                velocity.x = -velocity.x;
-                position.x = 0.0;
-            }
-            else if position.x + size_x >= window_width as f32
-            {
-                velocity.x = -velocity.x;
-                position.x = window_width as f32 - size_x;
-            }
-            
-            if position.y <= 0.0
-            {
-                velocity.y = -velocity.y;
-                position.y = 0.0;
-            }
-
-            self.game_object.set_velocity( velocity );
-            self.game_object.set_position( position );
-
-        }
-        return position;
-    }
-
-    pub fn reset( &mut self, position : glm::Vec2,  velocity : glm::Vec2) {
-        self.game_object.set_velocity( velocity );
-        self.game_object.set_position( position );
-    }
-    
-    pub fn check_collision( &self, check_obj : &GameObject) -> bool {
-        self.game_object.check_collision(check_obj)
-    }
-}
+use crate::game_object::GameObject;
+use crate::sprite::SpriteId;
+
+/// Side of the ball that touched an object, seen from the ball centre
+/// (screen coordinates, so `Up` points towards the top of the window)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Direction {
+    Up,
+    Right,
+    Down,
+    Left,
+}
+
+impl Direction {
+    /// compass direction that is closest to the given vector
+    pub fn from_vector(target: glm::Vec2) -> Self {
+        let compass = [
+            (Direction::Up, glm::vec2(0.0, -1.0)),
+            (Direction::Right, glm::vec2(1.0, 0.0)),
+            (Direction::Down, glm::vec2(0.0, 1.0)),
+            (Direction::Left, glm::vec2(-1.0, 0.0)),
+        ];
+
+        let target = if target.norm() > 0.0 { target.normalize() } else { target };
+        let mut best_match = (Direction::Up, f32::MIN);
+        for (direction, unit) in compass.iter() {
+            let dot = glm::dot(&target, unit);
+            if dot > best_match.1 {
+                best_match = (*direction, dot);
+            }
+        }
+        best_match.0
+    }
+}
+
+/// Result of a circle vs AABB test
+#[derive(Debug, Clone, Copy)]
+pub struct Collision {
+    /// side of the ball that made contact
+    pub direction: Direction,
+    /// vector from the ball centre to the closest point on the object
+    pub difference: glm::Vec2,
+    /// distance the ball has to move against `direction` to be clear of
+    /// the object again
+    pub penetration: f32,
+}
+
+#[derive(Debug, Clone)]
+pub struct BallObject {
+
+    game_object: GameObject,
+    radius: f32,
+    stuck : bool,
+    sticky : bool,
+    pass_through : bool,
+}
+
+impl BallObject {
+
+    pub fn new(
+        position : glm::Vec2,
+        radius   : f32,
+        velocity : glm::Vec2,
+        sprite : SpriteId ) -> Self {
+
+        Self {
+            game_object: GameObject::new(
+                position,
+                glm::vec2(radius * 2.0, radius * 2.0),
+                velocity,
+                glm::vec3(1.0, 1.0, 1.0),
+                0.0,
+                sprite,
+                false,
+            ),
+            radius,
+            stuck : true,
+            sticky : false,
+            pass_through : false,
+        }
+    }
+
+    pub fn game_object(&self) -> &GameObject {
+        &self.game_object
+    }
+
+    /// remember the current position as the start of the next update
+    pub fn save_position(&mut self) {
+        self.game_object.save_position();
+    }
+
+    pub fn un_stuck(&mut self) {
+        self.stuck = false;
+    }
+
+    pub fn is_stuck(&self) -> bool {
+        self.stuck
+    }
+
+    /// a sticky ball is caught by the paddle instead of bouncing off it
+    pub fn set_sticky(&mut self, sticky : bool) {
+        self.sticky = sticky;
+    }
+
+    /// a pass-through ball is not deflected by the bricks it can destroy
+    pub fn set_pass_through(&mut self, pass_through : bool) {
+        self.pass_through = pass_through;
+    }
+
+    pub fn is_pass_through(&self) -> bool {
+        self.pass_through
+    }
+
+    pub fn set_color(&mut self, color : glm::Vec3) {
+        self.game_object.set_color(color);
+    }
+
+    pub fn set_velocity(&mut self, velocity : glm::Vec2) {
+        self.game_object.set_velocity(velocity);
+    }
+
+    /// change the speed of the ball, keeping its direction
+    pub fn scale_velocity(&mut self, factor : f32) {
+        let velocity = self.game_object.get_velocity().scale(factor);
+        self.game_object.set_velocity(velocity);
+    }
+
+    /// move a stuck ball along with the paddle
+    pub fn follow(&mut self, dx : f32) {
+        if self.stuck {
+            let position = self.game_object.get_position() + glm::vec2(dx, 0.0);
+            self.game_object.set_position( position );
+        }
+    }
+
+    pub fn do_move( &mut self, dt : f32, window_width:u32) -> glm::Vec2 {
+        let mut position = self.game_object.get_position();
+        let size_x = self.game_object.get_size().x;
+
+        if !self.stuck
+        {
+            let mut velocity = self.game_object.get_velocity();
+
+            // move the ball
+            position += velocity.scale(dt);
+
+            // check if outside window bounds; if so, reverse velocity
+            // and restore at correct position
+            if position.x <= 0.0
+            {
+                velocity.x = -velocity.x;
+                position.x = 0.0;
+            }
+            else if position.x + size_x >= window_width as f32
+            {
+                velocity.x = -velocity.x;
+                position.x = window_width as f32 - size_x;
+            }
+
+            if position.y <= 0.0
+            {
+                velocity.y = -velocity.y;
+                position.y = 0.0;
+            }
+
+            self.game_object.set_velocity( velocity );
+            self.game_object.set_position( position );
+
+        }
+        position
+    }
+
+    /// put the ball back at the given position, stuck to the paddle
+    pub fn reset( &mut self, position : glm::Vec2,  velocity : glm::Vec2) {
+        self.game_object.set_velocity( velocity );
+        self.game_object.set_position( position );
+        self.game_object.save_position();
+        self.stuck = true;
+        self.sticky = false;
+        self.pass_through = false;
+    }
+
+    pub fn get_position(&self) -> glm::Vec2 {
+        self.game_object.get_position()
+    }
+
+    pub fn get_velocity(&self) -> glm::Vec2 {
+        self.game_object.get_velocity()
+    }
+
+    pub fn get_radius(&self) -> f32 {
+        self.radius
+    }
+
+    pub fn get_center(&self) -> glm::Vec2 {
+        self.game_object.get_position() + glm::vec2(self.radius, self.radius)
+    }
+
+    /// Circle vs AABB test, returns the contact side and the vector from the
+    /// ball centre to the closest point of the object
+    pub fn check_collision( &self, check_obj : &GameObject) -> Option<Collision> {
+        let center = self.get_center();
+
+        // centre and half extents of the object
+        let half_extents = check_obj.get_size().scale(0.5);
+        let aabb_center = check_obj.get_position() + half_extents;
+
+        // closest point on the object to the ball centre
+        let offset = center - aabb_center;
+        let clamped = glm::clamp_vec(&offset, &(-half_extents), &half_extents);
+        let closest = aabb_center + clamped;
+
+        let difference = closest - center;
+        if difference.norm() < self.radius {
+            // with the centre inside the object there is no closest side,
+            // the ball then hit the side it is moving towards
+            let direction = if difference.norm() > 0.0 {
+                Direction::from_vector(difference)
+            } else {
+                Direction::from_vector(self.game_object.get_velocity())
+            };
+            // distance from the centre to the side the ball leaves through,
+            // plus the radius, this also holds for a centre inside the object
+            let penetration = self.radius + match direction {
+                Direction::Left => half_extents.x - offset.x,
+                Direction::Right => half_extents.x + offset.x,
+                Direction::Up => half_extents.y - offset.y,
+                Direction::Down => half_extents.y + offset.y,
+            };
+            Some(Collision {
+                direction,
+                difference,
+                penetration,
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Reflect the velocity on the collision axis and push the ball out of
+    /// the object it penetrated
+    pub fn resolve_collision(&mut self, collision: &Collision) {
+        let mut position = self.game_object.get_position();
+        let mut velocity = self.game_object.get_velocity();
+
+        match collision.direction {
+            Direction::Left => {
+                velocity.x = velocity.x.abs();
+                position.x += collision.penetration;
+            },
+            Direction::Right => {
+                velocity.x = -velocity.x.abs();
+                position.x -= collision.penetration;
+            },
+            Direction::Up => {
+                velocity.y = velocity.y.abs();
+                position.y += collision.penetration;
+            },
+            Direction::Down => {
+                velocity.y = -velocity.y.abs();
+                position.y -= collision.penetration;
+            },
+        }
+
+        self.game_object.set_velocity( velocity );
+        self.game_object.set_position( position );
+    }
+
+    /// Bounce off the paddle, the further from the paddle centre the ball
+    /// lands the steeper the outgoing angle, the speed itself is unchanged
+    pub fn bounce_off_paddle(&mut self, paddle : &GameObject, base_velocity_x : f32, strength : f32) {
+        let half_width = paddle.get_size().x / 2.0;
+        let paddle_center = paddle.get_position().x + half_width;
+        let distance = self.get_center().x - paddle_center;
+        let percentage = (distance / half_width).clamp(-1.0, 1.0);
+
+        let old_velocity = self.game_object.get_velocity();
+        let speed = old_velocity.norm();
+
+        // always send the ball upwards, so it can never get stuck in the paddle
+        let mut velocity = glm::vec2(
+            base_velocity_x.abs() * percentage * strength,
+            -old_velocity.y.abs());
+        velocity = velocity.normalize().scale(speed);
+
+        // and place it on top of the paddle
+        let mut position = self.game_object.get_position();
+        position.y = paddle.get_position().y - self.radius * 2.0;
+
+        self.game_object.set_velocity( velocity );
+        self.game_object.set_position( position );
+        self.stuck = self.sticky;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sprite::Sprites;
+
+    const RADIUS : f32 = 10.0;
+
+    fn ball(center : glm::Vec2, velocity : glm::Vec2) -> BallObject {
+        let sprite = Sprites::new().register("ball", "ball.png");
+        let mut ball = BallObject::new(center - glm::vec2(RADIUS, RADIUS), RADIUS, velocity, sprite);
+        ball.un_stuck();
+        ball
+    }
+
+    /// a 100 x 20 brick at (100, 100)
+    fn brick() -> GameObject {
+        let sprite = Sprites::new().register("brick", "brick.png");
+        GameObject::new(glm::vec2(100.0, 100.0), glm::vec2(100.0, 20.0), glm::vec2(0.0, 0.0),
+            glm::vec3(1.0, 1.0, 1.0), 0.0, sprite, false)
+    }
+
+    #[test]
+    fn no_collision_when_apart() {
+        assert!(ball(glm::vec2(150.0, 85.0), glm::vec2(0.0, 100.0)).check_collision(&brick()).is_none());
+        // near the corner, but outside the circle
+        assert!(ball(glm::vec2(92.0, 92.0), glm::vec2(0.0, 100.0)).check_collision(&brick()).is_none());
+    }
+
+    #[test]
+    fn collision_direction_is_the_touching_side() {
+        let from_above = ball(glm::vec2(150.0, 95.0), glm::vec2(0.0, 100.0)).check_collision(&brick()).unwrap();
+        assert_eq!(from_above.direction, Direction::Down);
+        assert_eq!(from_above.difference, glm::vec2(0.0, 5.0));
+
+        let from_below = ball(glm::vec2(150.0, 125.0), glm::vec2(0.0, -100.0)).check_collision(&brick()).unwrap();
+        assert_eq!(from_below.direction, Direction::Up);
+
+        let from_left = ball(glm::vec2(95.0, 110.0), glm::vec2(100.0, 0.0)).check_collision(&brick()).unwrap();
+        assert_eq!(from_left.direction, Direction::Right);
+
+        let from_right = ball(glm::vec2(205.0, 110.0), glm::vec2(-100.0, 0.0)).check_collision(&brick()).unwrap();
+        assert_eq!(from_right.direction, Direction::Left);
+    }
+
+    #[test]
+    fn resolve_reflects_and_pushes_out() {
+        let mut ball = ball(glm::vec2(150.0, 95.0), glm::vec2(30.0, 100.0));
+        let collision = ball.check_collision(&brick()).unwrap();
+        ball.resolve_collision(&collision);
+
+        assert_eq!(ball.get_velocity(), glm::vec2(30.0, -100.0));
+        // the ball now rests on top of the brick
+        assert_eq!(ball.get_center(), glm::vec2(150.0, 90.0));
+        assert!(ball.check_collision(&brick()).is_none());
+
+        let mut ball = self::ball(glm::vec2(95.0, 110.0), glm::vec2(100.0, 30.0));
+        let collision = ball.check_collision(&brick()).unwrap();
+        ball.resolve_collision(&collision);
+        assert_eq!(ball.get_velocity(), glm::vec2(-100.0, 30.0));
+        assert_eq!(ball.get_center(), glm::vec2(90.0, 110.0));
+    }
+
+    #[test]
+    fn centre_inside_the_object_uses_the_velocity() {
+        // a fast ball whose centre ended up inside the brick
+        let falling = ball(glm::vec2(150.0, 105.0), glm::vec2(10.0, 100.0)).check_collision(&brick()).unwrap();
+        assert_eq!(falling.difference, glm::vec2(0.0, 0.0));
+        assert_eq!(falling.direction, Direction::Down);
+
+        let rising = ball(glm::vec2(150.0, 105.0), glm::vec2(10.0, -100.0)).check_collision(&brick()).unwrap();
+        assert_eq!(rising.direction, Direction::Up);
+
+        let mut moving_left = ball(glm::vec2(150.0, 105.0), glm::vec2(-100.0, 10.0));
+        let collision = moving_left.check_collision(&brick()).unwrap();
+        assert_eq!(collision.direction, Direction::Left);
+        moving_left.resolve_collision(&collision);
+        assert_eq!(moving_left.get_velocity(), glm::vec2(100.0, 10.0));
+        // pushed out through the right side of the brick
+        assert_eq!(moving_left.get_center(), glm::vec2(210.0, 105.0));
+        assert!(moving_left.check_collision(&brick()).is_none());
+
+        let mut falling = ball(glm::vec2(150.0, 105.0), glm::vec2(10.0, 100.0));
+        let collision = falling.check_collision(&brick()).unwrap();
+        falling.resolve_collision(&collision);
+        assert_eq!(falling.get_velocity(), glm::vec2(10.0, -100.0));
+        assert_eq!(falling.get_center(), glm::vec2(150.0, 90.0));
+        assert!(falling.check_collision(&brick()).is_none());
+    }
+}
@@ -0,0 +1,96 @@
+use thiserror::Error;
+
+/// Reasons a shader program can fail to build
+#[derive(Debug, Error)]
+pub enum ShaderError {
+    #[error("cannot create GL object: {0}")]
+    Create(String),
+    #[error("{stage} shader failed to compile:\n{log}")]
+    Compile { stage : &'static str, log : String },
+    #[error("program failed to link:\n{log}")]
+    Link { log : String },
+}
+
+/// Reasons a replay does not play back like it was recorded
+#[derive(Debug, Error)]
+pub enum ReplayError {
+    #[error("replay desynced, the final state has checksum {actual:016x} instead of {expected:016x}")]
+    Desync { expected : u64, actual : u64 },
+}
+
+/// Failure to load one of the game assets
+#[derive(Debug, Error)]
+pub enum ResourceError {
+    #[error("cannot read '{path}'")]
+    Io {
+        path : String,
+        #[source]
+        source : std::io::Error,
+    },
+    #[error("cannot decode image '{path}'")]
+    Image {
+        path : String,
+        #[source]
+        source : image::ImageError,
+    },
+    #[error("cannot write image '{path}'")]
+    ImageWrite {
+        path : String,
+        #[source]
+        source : image::ImageError,
+    },
+    #[error("cannot write '{path}'")]
+    Write {
+        path : String,
+        #[source]
+        source : std::io::Error,
+    },
+    #[error("cannot upload texture '{path}': {message}")]
+    Texture { path : String, message : String },
+    #[error("cannot build texture atlas '{name}': {message}")]
+    Atlas { name : String, message : String },
+    #[error("cannot build shader '{name}' from '{vertex_path}' and '{fragment_path}'")]
+    Shader {
+        name : String,
+        vertex_path : String,
+        fragment_path : String,
+        #[source]
+        source : ShaderError,
+    },
+    #[error("level '{path}' line {line}: invalid brick '{token}'")]
+    LevelParse { path : String, line : usize, token : String },
+    #[error("level '{path}' is not a valid level file")]
+    LevelFormat {
+        path : String,
+        #[source]
+        source : toml::de::Error,
+    },
+    #[error("level '{path}': {message}")]
+    LevelData { path : String, message : String },
+    #[error("level '{path}' contains no bricks")]
+    EmptyLevel { path : String },
+    #[error("cannot load font '{name}': {message}")]
+    Font { name : String, message : String },
+    #[error("cannot decode sound '{path}': {message}")]
+    Audio { path : String, message : String },
+    #[error("high score file '{path}' is corrupted, it was moved to '{backup}': {message}")]
+    CorruptScores { path : String, backup : String, message : String },
+    #[error("replay '{path}': {message}")]
+    Replay { path : String, message : String },
+    #[error("cannot read asset archive '{path}': {message}")]
+    Archive { path : String, message : String },
+    #[error("asset '{0}' not found")]
+    MissingAsset(String),
+    #[error("cannot open the audio output: {0}")]
+    AudioDevice(String),
+    #[error("cannot create framebuffer: {0}")]
+    Framebuffer(String),
+    #[error("texture '{0}' is not loaded")]
+    MissingTexture(String),
+    #[error("shader '{0}' is not loaded")]
+    MissingShader(String),
+    #[error("texture atlas '{0}' is not loaded")]
+    MissingAtlas(String),
+    #[error("font '{0}' is not loaded")]
+    MissingFont(String),
+}
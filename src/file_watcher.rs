@@ -0,0 +1,148 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::assets::Assets;
+
+// how often the files are checked, editors save in several steps so this
+// also keeps a half written file from being picked up too often
+const POLL_INTERVAL : Duration = Duration::from_millis(500);
+
+/// Notices changes to assets by comparing the modification times of their
+/// files in the override directory, for reloading resources while the game
+/// runs. Built in assets never change.
+#[derive(Debug)]
+pub struct FileWatcher {
+    assets : Rc<Assets>,
+    files : HashMap<String, Option<SystemTime>>,
+    last_poll : Instant,
+}
+
+impl FileWatcher {
+    pub fn new(assets : Rc<Assets>) -> Self {
+        Self {
+            assets,
+            files : HashMap::new(),
+            last_poll : Instant::now(),
+        }
+    }
+
+    /// start watching the asset, watching it again changes nothing
+    pub fn watch(&mut self, path : &str) {
+        if !self.files.contains_key(path) {
+            let modified = self.assets.modified(path);
+            self.files.insert(path.into(), modified);
+        }
+    }
+
+    pub fn watch_all<P : AsRef<str>>(&mut self, paths : impl IntoIterator<Item = P>) {
+        for path in paths {
+            self.watch(path.as_ref());
+        }
+    }
+
+    /// true once POLL_INTERVAL passed since the last poll, so the files to
+    /// watch only need to be gathered when `poll` will look at them
+    pub fn is_due(&self) -> bool {
+        self.last_poll.elapsed() >= POLL_INTERVAL
+    }
+
+    /// the assets that changed or appeared since the last poll, at most one
+    /// check per POLL_INTERVAL. A deleted file is reported once it is back.
+    pub fn poll(&mut self) -> Vec<String> {
+        if !self.is_due() {
+            return Vec::new();
+        }
+        self.last_poll = Instant::now();
+        self.changed_files()
+    }
+
+    fn changed_files(&mut self) -> Vec<String> {
+        let mut changed = Vec::new();
+        for (path, last_modified) in self.files.iter_mut() {
+            let modified = self.assets.modified(path);
+            if modified != *last_modified {
+                *last_modified = modified;
+                if modified.is_some() {
+                    changed.push(path.clone());
+                }
+            }
+        }
+        changed.sort();
+        changed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+    use crate::test_util::TempDir;
+
+    const LEVEL : &str = "levels/one.lvl";
+
+    /// override directory for one test with a `levels` folder
+    fn level_dir(name : &str) -> TempDir {
+        let dir = TempDir::new(name);
+        std::fs::create_dir_all(dir.path().join("levels")).unwrap();
+        dir
+    }
+
+    fn watcher(dir : &TempDir) -> FileWatcher {
+        let mut file_watcher = FileWatcher::new(Rc::new(Assets::with_override(dir.path()).unwrap()));
+        file_watcher.watch(LEVEL);
+        file_watcher
+    }
+
+    /// write the file with a modification time `seconds` after the epoch,
+    /// file systems may not notice two writes within the same tick
+    fn write(path : &Path, seconds : u64) {
+        std::fs::write(path, "1 1 1").unwrap();
+        let file = std::fs::File::options().write(true).open(path).unwrap();
+        file.set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(seconds)).unwrap();
+    }
+
+    #[test]
+    fn changed_file_is_reported_once() {
+        let dir = level_dir("watch_change");
+        let path = dir.path().join(LEVEL);
+        write(&path, 1000);
+        let mut file_watcher = watcher(&dir);
+        assert!(file_watcher.changed_files().is_empty());
+
+        write(&path, 2000);
+        assert_eq!(file_watcher.changed_files(), [LEVEL]);
+        assert!(file_watcher.changed_files().is_empty());
+    }
+
+    #[test]
+    fn deleted_file_is_reported_when_it_is_back() {
+        let dir = level_dir("watch_reappear");
+        let path = dir.path().join(LEVEL);
+        write(&path, 1000);
+        let mut file_watcher = watcher(&dir);
+
+        std::fs::remove_file(&path).unwrap();
+        assert!(file_watcher.changed_files().is_empty());
+        write(&path, 1000);
+        assert_eq!(file_watcher.changed_files(), [LEVEL]);
+    }
+
+    #[test]
+    fn new_file_is_reported() {
+        let dir = level_dir("watch_new");
+        let mut file_watcher = watcher(&dir);
+        assert!(file_watcher.changed_files().is_empty());
+        write(&dir.path().join(LEVEL), 1000);
+        assert_eq!(file_watcher.changed_files(), [LEVEL]);
+    }
+
+    #[test]
+    fn poll_waits_for_the_interval() {
+        let dir = level_dir("watch_interval");
+        let mut file_watcher = watcher(&dir);
+        assert!(!file_watcher.is_due());
+        write(&dir.path().join(LEVEL), 1000);
+        assert!(file_watcher.poll().is_empty());
+    }
+}
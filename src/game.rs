@@ -1,252 +1,1199 @@
-//use glow::*;
-use std::{borrow::{Borrow, BorrowMut}, rc::Rc};
-use glutin::event::VirtualKeyCode;
-use crate::game_object::GameObject;
-use crate::sprite_renderer::SpriteRenderer;
-use crate::resource_manager::ResourceManager;
-use crate::texture::Texture;
-use crate::game_level::GameLevel;
-use crate::ball_object::BallObject;
-
-
-extern crate nalgebra_glm as glm;
-
-#[derive(Debug,PartialEq)]
-enum GameState {
-    GameActive,
-    GameMenu,
-    GameWin
-}
-
-struct GlObjs{
-    sprite_renderer  : SpriteRenderer,
-    face_texture : Rc<Texture>,
-    background_texture : Rc<Texture>,
-    player : GameObject,
-    ball : BallObject,
-    game_levels : Vec<GameLevel>,
-    level      : usize,  
-}
-
-pub struct Game {
-    resource_manager : Rc<ResourceManager>,
-    game_state: GameState,
-    keys : [bool; 1024],
-    width : u32,
-    height : u32,
-    globjs : Option<GlObjs>,
-}
-
-impl Game {
-    pub fn new(resource_manager : Rc<ResourceManager>, width:u32, height: u32) -> Self {
-        Self {
-            resource_manager,
-            game_state : GameState::GameActive,
-            keys : [false;1024],
-            width,
-            height,
-            globjs : None,
-        }
-    }
-
-    pub fn init(&mut self) 
-    {
-        let sprite_shader = self.resource_manager.load_shader(  
-            "src/shaders/sprite.vs",
-            "src/shaders/sprite.fs",
-            "sprite".into()
-        );
-
-        let projection = glm::ortho(
-            0.0, 
-            self.width as f32,
-            self.height as f32,
-            0.0,
-            -1.0,
-            1.0);
-      
-        // select shader before setting uniforms
-        sprite_shader.use_program();
-        // AF TODO REMOVE let projection = glm::Mat4::identity();
-        sprite_shader.set_uniform_i32("image", 0);
-        sprite_shader.set_uniform_mat4("projection", &projection);
-
-        let sprite_renderer = SpriteRenderer::new(
-            self.resource_manager.get_gl(),
-            sprite_shader,
-        );
-
-        let background_texture = self.resource_manager.load_texture(
-            "resources/textures/background.jpg", 
-            "background".into(),
-        );
-
-        let face_texture = self.resource_manager.load_texture(
-            "resources/textures/awesomeface.png", 
-            "face".into(),
-        );
-
-
-        let paddle_texture = self.resource_manager.load_texture(
-            "resources/textures/paddle.png", 
-            "paddle".into(),
-        );
-
-        const PLAYER_SIZE : glm::Vec2 = glm::Vec2::new(100.0, 20.0); 
-        let player_position = glm::vec2(
-            (self.width as f32 - PLAYER_SIZE.x) / 2.0,
-            self.height as f32 - PLAYER_SIZE.y );
-             
-
-        let player = GameObject::new(
-            player_position,
-            glm::vec2(100.0,20.0),
-            glm::vec2(500.0,0.0),
-            glm::vec3(1.0,1.0,1.0),
-            0.0,
-            paddle_texture, 
-            false);
-
-        let mut game_levels = Vec::new();
-
-        let mut game_level = GameLevel::new(self.resource_manager.clone());
-        game_level.load("levels/one.lvl" , self.width, self.height/2);
-        game_levels.push(game_level);
-
-        let mut game_level = GameLevel::new(self.resource_manager.clone());
-        game_level.load("levels/two.lvl" , self.width, self.height/2);
-        game_levels.push(game_level);
-
-        let mut game_level = GameLevel::new(self.resource_manager.clone());
-        game_level.load("levels/three.lvl" , self.width, self.height/2);
-        game_levels.push(game_level);
-
-        let mut game_level = GameLevel::new(self.resource_manager.clone());
-        game_level.load("levels/four.lvl" , self.width, self.height/2);
-        game_levels.push(game_level);
-
-
-        // Initial velocity of the Ball
-        const INITIAL_BALL_VELOCITY : glm::Vec2 = glm::Vec2::new(100.0, -350.0);
-        // Radius of the ball object
-        const BALL_RADIUS : f32 = 12.5;
-
-        let ball_position = player_position + 
-            glm::vec2(PLAYER_SIZE.x / 2.0 - BALL_RADIUS, -BALL_RADIUS * 2.0);
-
-        let ball_texture = self.resource_manager.load_texture(
-            "resources/textures/awesomeface.png", 
-            "face".into(),
-        );
-
-        let ball = BallObject::new(
-            ball_position,
-            BALL_RADIUS,
-            INITIAL_BALL_VELOCITY,
-            ball_texture ); 
-
-        self.globjs = Some( GlObjs {
-            sprite_renderer,
-            face_texture,
-            background_texture,
-            player,
-            ball, 
-            game_levels,
-            level : 0,
-        } );
-    }
-
-    pub fn process_input(&mut self, dt: f32, key : VirtualKeyCode) {
-
-        if self.game_state != GameState::GameActive {
-            return;
-        }
-        if let Some(objs) = self.globjs.borrow_mut() {
-            let mut position = objs.player.get_position();
-            let width_div_2 = objs.player.get_size().x as f32 / 2.0;
-
-            let velocity = objs.player.get_velocity().x as f32 * dt;
-
-            match key {
-                VirtualKeyCode::Left | VirtualKeyCode::A    => {
-                    position.x = ( position.x - velocity ).max(- width_div_2);
-                    objs.player.set_position(position);
-                },
-                VirtualKeyCode::Right | VirtualKeyCode::D    => {
-                    position.x = ( position.x + velocity ).min(self.width as f32 - width_div_2);
-                    objs.player.set_position(position);
-                },
-                VirtualKeyCode::Space => {
-                    objs.ball.un_stuck();
-                },                
-                _ => (),
-            }
-        }
-    }
-
-    pub fn update(&mut self, dt: f32) {
-        if let Some(objs) = self.globjs.borrow_mut() {
-            objs.ball.do_move(dt, self.width as u32);
-        }
-        self.do_collisions();
-    }
-
-    pub fn render( &mut self, _dt: f32) {
-        if let Some(objs) = &self.globjs {
-            // draw background
-            objs.sprite_renderer.draw(
-                objs.background_texture.as_ref(),
-                glm::vec2(0.0, 0.0),
-                glm::vec2(self.width as f32, self.height as f32),
-                0.0,
-                glm::vec3(1.0,  1.0, 1.0)
-
-            );
-
-            let position = glm::vec2(200.0f32, 200.0f32);
-            let rotate = 45.0f32;
-            let size = glm::vec2(300.0f32, 400.0f32);
-            let color = glm::vec3(0.0f32, 1.0f32,0.0f32);
-
-            objs.sprite_renderer.draw(
-                objs.face_texture.as_ref(),
-                position,
-                size,
-                rotate,
-                color
-            );
-            objs.game_levels[objs.level].draw(&objs.sprite_renderer);
-            objs.player.draw(&objs.sprite_renderer);
-            objs.ball.draw(&objs.sprite_renderer);
-
-        }
-    }
-
-    fn do_collisions(&mut self)
-    {
-        if let Some(objs) = self.globjs.borrow_mut() {
-            objs.game_levels[objs.level].update_ball_collisions(&objs.ball)
-        }
-
-    // // draw background
-
-    //     for (GameObject &box : Levels[Level].Bricks)
-    // {
-    // if (!box.Destroyed)
-    // {
-    // if (CheckCollision(*Ball, box))
-    // {
-    // if (!box.IsSolid)
-    // box.Destroyed = true;
-    // }
-    // }
-    // }
-    }
-
-}
-
-impl Drop for Game {
-    fn drop(&mut self) {
-
-    }
-} 
+use glutin::event::{ElementState, MouseButton, VirtualKeyCode};
+use crate::game_object::GameObject;
+use crate::game_level::GameLevel;
+use crate::ball_object::BallObject;
+use crate::input::InputState;
+use crate::power_up::{ActivePowerUp, PowerUp, PowerUpKind};
+use crate::particle_generator::ParticleGenerator;
+use crate::scoring::Scoring;
+use crate::high_scores::{HighScores, MAX_NAME_LENGTH};
+use crate::replay::StateHasher;
+use crate::sprite::{SpriteId, Sprites};
+use crate::assets::Assets;
+use crate::error::ResourceError;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use std::hash::{Hash, Hasher};
+use std::path::PathBuf;
+use std::rc::Rc;
+
+extern crate nalgebra_glm as glm;
+
+// Size of the player paddle
+const PLAYER_SIZE : glm::Vec2 = glm::Vec2::new(100.0, 20.0);
+// Initial velocity of the Ball
+const INITIAL_BALL_VELOCITY : glm::Vec2 = glm::Vec2::new(100.0, -350.0);
+// Radius of the ball object
+const BALL_RADIUS : f32 = 12.5;
+// How strongly the hit position on the paddle steers the ball
+const PADDLE_BOUNCE_STRENGTH : f32 = 2.0;
+// Number of lives the player starts a level with
+const INITIAL_LIVES : u32 = 3;
+// Chance that a destroyed brick drops a power-up, unless the level sets it
+const POWER_UP_CHANCE : f32 = 0.1;
+// Ball speed factor of the speed power-up
+const SPEED_UP_FACTOR : f32 = 1.2;
+// Extra paddle width of the paddle grow power-up
+const PADDLE_GROW_WIDTH : f32 = 50.0;
+// Number of balls a multi-ball power-up splits a ball into
+const MULTI_BALL_COUNT : usize = 3;
+// Angle between the balls of a split, in degrees
+const MULTI_BALL_SPREAD : f32 = 20.0;
+// How long the screen shakes after the ball hits a solid brick, in seconds
+const SHAKE_DURATION : f32 = 0.05;
+// Number of particles for the ball trails and brick debris
+const PARTICLE_COUNT : usize = 500;
+// Seed of the random numbers, so a game can be played back exactly
+const RANDOM_SEED : u64 = 0x5eed;
+
+#[allow(clippy::enum_variant_names)]
+#[derive(Debug,PartialEq,Eq,Hash,Clone,Copy)]
+pub enum GameState {
+    GameActive,
+    GameMenu,
+    GameWin,
+    GameOver,
+}
+
+/// Something that happened in the simulation, collected during the updates
+/// so sound and other feedback can react without the game knowing about it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GameEvent {
+    BrickHit { solid : bool, destroyed : bool },
+    PaddleHit,
+    PowerUpCollected(PowerUpKind),
+    BallLost,
+    StateChanged(GameState),
+}
+
+/// The game simulation, it holds no GL resources so it can run and be
+/// inspected without a window, `GameRenderer` draws it
+pub struct Game {
+    assets : Rc<Assets>,
+    game_state: GameState,
+    input : InputState,
+    width : u32,
+    height : u32,
+    lives : u32,
+    scoring : Scoring,
+    high_scores : HighScores,
+    high_score_path : Option<PathBuf>,
+    name_entry : Option<String>,
+    new_best_time : bool,
+    sprites : Sprites,
+    background_sprite : SpriteId,
+    player : GameObject,
+    /// x the paddle centre moves to, set by the mouse
+    paddle_target : Option<f32>,
+    balls : Vec<BallObject>,
+    game_levels : Vec<GameLevel>,
+    level      : usize,
+    /// random numbers that change how the game goes, particles use their own
+    rng : ChaCha8Rng,
+    power_up_sprite : SpriteId,
+    power_up_chance : Option<f32>,
+    power_ups : Vec<PowerUp>,
+    active_power_ups : Vec<ActivePowerUp>,
+    confuse : bool,
+    chaos : bool,
+    shake_time : f32,
+    time : f32,
+    ticks : u64,
+    particles : ParticleGenerator,
+    events : Vec<GameEvent>,
+}
+
+impl Game {
+    /// a game with the assets built into the binary
+    pub fn new(width:u32, height: u32) -> Self {
+        Self::with_assets(width, height, Rc::new(Assets::embedded()))
+    }
+
+    /// a game that loads its levels from `assets`
+    pub fn with_assets(width:u32, height: u32, assets : Rc<Assets>) -> Self {
+        let mut sprites = Sprites::new();
+        let background_sprite = sprites.register("background", "resources/textures/background.jpg");
+        let paddle_sprite = sprites.register("paddle", "resources/textures/paddle.png");
+        let ball_sprite = sprites.register("face", "resources/textures/awesomeface.png");
+        let particle_sprite = sprites.register("particle", "resources/textures/particle.png");
+        let power_up_sprite = sprites.register("power_up", "resources/textures/block.png");
+
+        let player_position = glm::vec2(
+            (width as f32 - PLAYER_SIZE.x) / 2.0,
+            height as f32 - PLAYER_SIZE.y );
+
+        let player = GameObject::new(
+            player_position,
+            PLAYER_SIZE,
+            glm::vec2(500.0,0.0),
+            glm::vec3(1.0,1.0,1.0),
+            0.0,
+            paddle_sprite,
+            false);
+
+        let ball_position = player_position +
+            glm::vec2(PLAYER_SIZE.x / 2.0 - BALL_RADIUS, -BALL_RADIUS * 2.0);
+
+        let ball = BallObject::new(
+            ball_position,
+            BALL_RADIUS,
+            INITIAL_BALL_VELOCITY,
+            ball_sprite );
+
+        Self {
+            assets,
+            game_state : GameState::GameMenu,
+            input : InputState::new(),
+            width,
+            height,
+            lives : INITIAL_LIVES,
+            scoring : Scoring::new(),
+            high_scores : HighScores::new(),
+            high_score_path : None,
+            name_entry : None,
+            new_best_time : false,
+            sprites,
+            background_sprite,
+            player,
+            paddle_target : None,
+            balls : vec![ball],
+            game_levels : Vec::new(),
+            level : 0,
+            rng : ChaCha8Rng::seed_from_u64(RANDOM_SEED),
+            power_up_sprite,
+            power_up_chance : None,
+            power_ups : Vec::new(),
+            active_power_ups : Vec::new(),
+            confuse : false,
+            chaos : false,
+            shake_time : 0.0,
+            time : 0.0,
+            ticks : 0,
+            particles : ParticleGenerator::new(PARTICLE_COUNT, particle_sprite),
+            events : Vec::new(),
+        }
+    }
+
+    pub fn init(&mut self) -> Result<(), ResourceError>
+    {
+        for level_path in ["levels/one.lvl", "levels/two.lvl", "levels/three.lvl", "levels/four.lvl", "levels/five.toml"] {
+            self.add_level(level_path)?;
+        }
+        self.enter_state(self.game_state);
+        Ok(())
+    }
+
+    /// load a level file and add it to the levels that can be selected
+    pub fn add_level(&mut self, level_path : &str) -> Result<(), ResourceError> {
+        let mut game_level = GameLevel::new();
+        game_level.load(&self.assets, level_path, self.width, self.height/2, &mut self.sprites)?;
+        self.game_levels.push(game_level);
+        Ok(())
+    }
+
+    /// the assets the levels were loaded from
+    pub fn level_files(&self) -> Vec<String> {
+        self.game_levels.iter().map(|level| level.path().to_string()).collect()
+    }
+
+    /// load the levels made from any of the `changed` assets again, with all
+    /// bricks restored. A level that fails to load keeps its bricks.
+    pub fn reload_levels(&mut self, changed : &[String]) {
+        for level in self.game_levels.iter_mut() {
+            if !changed.iter().any(|path| path == level.path()) {
+                continue;
+            }
+            match level.reload(&self.assets, &mut self.sprites) {
+                Ok(()) => log::info!("Reloaded level {}", level.path()),
+                Err(err) => eprintln!("Error: {:#}", anyhow::Error::new(err)),
+            }
+        }
+    }
+
+    /// switch to a new state, running the exit hook of the current state
+    /// and the enter hook of the new one
+    fn set_state(&mut self, game_state : GameState) {
+        if game_state == self.game_state {
+            return;
+        }
+        log::debug!("Game state {:?} -> {:?}", self.game_state, game_state);
+        self.exit_state(self.game_state);
+        self.game_state = game_state;
+        self.events.push(GameEvent::StateChanged(game_state));
+        self.enter_state(game_state);
+    }
+
+    fn enter_state(&mut self, game_state : GameState) {
+        match game_state {
+            GameState::GameMenu => {
+                self.reset_player();
+            },
+            GameState::GameActive => {
+                // the level was restored when the win or game over screen
+                // was left, so the menu already shows every level complete
+                self.lives = INITIAL_LIVES;
+                self.scoring.reset();
+                self.reset_player();
+            },
+            GameState::GameWin => {
+                self.reset_player();
+                self.level_cleared();
+                self.start_name_entry();
+            },
+            GameState::GameOver => {
+                self.reset_player();
+                self.start_name_entry();
+            },
+        }
+    }
+
+    fn exit_state(&mut self, game_state : GameState) {
+        match game_state {
+            GameState::GameWin => {
+                // restore the cleared level and continue with the next one,
+                // which is complete as every level is restored when left
+                self.reset_level();
+                if !self.game_levels.is_empty() {
+                    self.level = (self.level + 1) % self.game_levels.len();
+                }
+            },
+            GameState::GameOver => {
+                self.reset_level();
+            },
+            GameState::GameMenu | GameState::GameActive => {},
+        }
+    }
+
+    /// feed a keyboard event into the input state
+    pub fn process_key(&mut self, key : VirtualKeyCode, state : ElementState) {
+        self.input.process_key(key, state);
+    }
+
+    /// feed a mouse button event into the input state
+    pub fn process_mouse_button(&mut self, button : MouseButton, state : ElementState) {
+        self.input.process_mouse_button(button, state);
+    }
+
+    /// feed the cursor position in logical playfield coordinates, `None`
+    /// when it left the window
+    pub fn process_pointer(&mut self, position : Option<glm::Vec2>) {
+        self.input.process_pointer(position);
+    }
+
+    /// forget all held keys, e.g. when the window loses focus
+    pub fn release_keys(&mut self) {
+        self.input.clear();
+    }
+
+    /// handle the keys pressed since the last frame
+    pub fn process_input(&mut self) {
+
+        match self.game_state {
+            GameState::GameMenu => {
+                self.process_menu_input();
+            },
+            GameState::GameWin | GameState::GameOver => {
+                if self.name_entry.is_some() {
+                    self.process_name_input();
+                } else if self.input.is_pressed(VirtualKeyCode::Return) {
+                    self.set_state(GameState::GameMenu);
+                }
+            },
+            GameState::GameActive => {
+                if self.input.is_pressed(VirtualKeyCode::Space) || self.input.is_button_pressed(MouseButton::Left) {
+                    self.balls.iter_mut().for_each(|ball| ball.un_stuck());
+                }
+                // the paddle follows the mouse once it moves
+                if self.input.is_pointer_moved() {
+                    self.paddle_target = self.input.pointer().map(|pointer| pointer.x);
+                }
+            },
+        }
+        self.input.end_frame();
+    }
+
+    /// feed a typed character into the name entry, if it is open
+    pub fn process_char(&mut self, c : char) {
+        if let Some(name) = self.name_entry.as_mut() {
+            let allowed = c.is_ascii_alphanumeric() || c == ' ' || c == '-' || c == '_';
+            if allowed && name.chars().count() < MAX_NAME_LENGTH {
+                name.push(c);
+            }
+        }
+    }
+
+    /// Backspace removes the last character and Enter puts the name in the
+    /// high score table
+    fn process_name_input(&mut self) {
+        if self.input.is_pressed(VirtualKeyCode::Back) {
+            if let Some(name) = self.name_entry.as_mut() {
+                name.pop();
+            }
+        } else if self.input.is_pressed(VirtualKeyCode::Return) {
+            let name = self.name_entry.take().unwrap_or_default();
+            let name = if name.trim().is_empty() { "PLAYER".to_string() } else { name };
+            let level = self.level_name();
+            self.high_scores.add(&name, self.scoring.score(), &level);
+            self.save_high_scores();
+        }
+    }
+
+    /// select one of the loaded levels and start it with Enter
+    fn process_menu_input(&mut self) {
+        let level_count = self.game_levels.len();
+        if level_count == 0 {
+            return;
+        }
+        if self.input.is_pressed(VirtualKeyCode::Return) {
+            self.set_state(GameState::GameActive);
+        } else if self.input.any_pressed(&[VirtualKeyCode::Up, VirtualKeyCode::W]) {
+            self.level = (self.level + 1) % level_count;
+        } else if self.input.any_pressed(&[VirtualKeyCode::Down, VirtualKeyCode::S]) {
+            self.level = (self.level + level_count - 1) % level_count;
+        }
+    }
+
+    /// move the paddle for as long as a direction key is held down, or
+    /// towards the mouse at the same speed
+    fn move_player(&mut self, dt : f32) {
+        let mut left = self.input.any_held(&[VirtualKeyCode::Left, VirtualKeyCode::A]);
+        let mut right = self.input.any_held(&[VirtualKeyCode::Right, VirtualKeyCode::D]);
+
+        let mut position = self.player.get_position();
+        let width_div_2 = self.player.get_size().x / 2.0;
+
+        let mut velocity = self.player.get_velocity().x * dt;
+
+        if left || right {
+            // the keys take over from the mouse
+            self.paddle_target = None;
+        } else if let Some(target) = self.paddle_target {
+            let distance = target - (position.x + width_div_2);
+            velocity = velocity.min(distance.abs());
+            left = distance < 0.0;
+            right = distance > 0.0;
+        }
+
+        let old_x = position.x;
+        if left {
+            position.x = ( position.x - velocity ).max(- width_div_2);
+        }
+        if right {
+            position.x = ( position.x + velocity ).min(self.width as f32 - width_div_2);
+        }
+        self.player.set_position(position);
+
+        // a stuck ball rides along with the paddle
+        self.balls.iter_mut().for_each(|ball| ball.follow(position.x - old_x));
+    }
+
+    pub fn update(&mut self, dt: f32) {
+        self.time += dt;
+        self.ticks += 1;
+        if self.game_state != GameState::GameActive {
+            return;
+        }
+        self.scoring.update(dt);
+        self.shake_time = (self.shake_time - dt).max(0.0);
+
+        self.player.save_position();
+        self.balls.iter_mut().for_each(|ball| ball.save_position());
+
+        self.move_player(dt);
+        let width = self.width;
+        self.balls.iter_mut().for_each(|ball| { ball.do_move(dt, width); });
+        self.do_collisions();
+        self.update_power_ups(dt);
+
+        self.particles.update(dt);
+        for ball in self.balls.iter() {
+            self.particles.emit_trail(ball);
+        }
+
+        // balls that leave through the bottom are gone, the life is only
+        // lost with the last one, which is kept to be reset on the paddle
+        let height = self.height as f32;
+        if self.balls.iter().all(|ball| ball.get_position().y >= height) {
+            self.lose_life();
+        } else {
+            self.balls.retain(|ball| ball.get_position().y < height);
+        }
+
+        if self.is_level_complete() {
+            self.set_state(GameState::GameWin);
+        }
+    }
+
+    pub fn state(&self) -> GameState {
+        self.game_state
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// number of lives left in the current level
+    pub fn lives(&self) -> u32 {
+        self.lives
+    }
+
+    pub fn score(&self) -> u32 {
+        self.scoring.score()
+    }
+
+    /// score, combo and time of the level being played
+    pub fn scoring(&self) -> &Scoring {
+        &self.scoring
+    }
+
+    pub fn high_scores(&self) -> &HighScores {
+        &self.high_scores
+    }
+
+    /// the name typed so far, while a new high score is being entered
+    pub fn name_entry(&self) -> Option<&str> {
+        self.name_entry.as_deref()
+    }
+
+    /// true when the level just cleared was faster than ever before
+    pub fn is_new_best_time(&self) -> bool {
+        self.new_best_time
+    }
+
+    /// name the current level is known by in the high score table
+    pub fn level_name(&self) -> String {
+        self.current_level().map(|level| level.header().name.clone()).unwrap_or_default()
+    }
+
+    /// use this high score table without saving it, e.g. to play a replay
+    pub fn set_high_scores(&mut self, high_scores : HighScores) {
+        self.high_scores = high_scores;
+        self.high_score_path = None;
+    }
+
+    /// read the high score table from `path` and save it there from now on,
+    /// without a file the table only lasts as long as the game
+    pub fn load_high_scores(&mut self, path : PathBuf) -> Result<(), ResourceError> {
+        self.high_scores = HighScores::load(&path)?;
+        self.high_score_path = Some(path);
+        Ok(())
+    }
+
+    pub fn sprites(&self) -> &Sprites {
+        &self.sprites
+    }
+
+    /// background of the current level, or the default one
+    pub fn background_sprite(&self) -> SpriteId {
+        self.current_level()
+            .and_then(|level| level.background_sprite())
+            .unwrap_or(self.background_sprite)
+    }
+
+    pub fn player(&self) -> &GameObject {
+        &self.player
+    }
+
+    /// the balls in play, there is always at least one
+    pub fn balls(&self) -> &[BallObject] {
+        &self.balls
+    }
+
+    /// pickups that are falling down
+    pub fn power_ups(&self) -> &[PowerUp] {
+        &self.power_ups
+    }
+
+    /// power-up effects that are running
+    pub fn active_power_ups(&self) -> &[ActivePowerUp] {
+        &self.active_power_ups
+    }
+
+    /// true while a confuse power-up is active
+    pub fn is_confused(&self) -> bool {
+        self.confuse
+    }
+
+    /// true while a chaos power-up is active
+    pub fn is_chaos(&self) -> bool {
+        self.chaos
+    }
+
+    /// the events since the last call, oldest first
+    pub fn take_events(&mut self) -> Vec<GameEvent> {
+        std::mem::take(&mut self.events)
+    }
+
+    /// ball trails and brick debris
+    pub fn particles(&self) -> &ParticleGenerator {
+        &self.particles
+    }
+
+    /// true for a moment after the ball hit a solid brick
+    pub fn is_shaking(&self) -> bool {
+        self.shake_time > 0.0
+    }
+
+    /// seconds of simulation since the game started
+    pub fn time(&self) -> f32 {
+        self.time
+    }
+
+    /// number of updates since the game started
+    pub fn ticks(&self) -> u64 {
+        self.ticks
+    }
+
+    /// hash of everything that decides how the game goes on, two games that
+    /// got the same input end with the same checksum. Particles are left
+    /// out, they only show what happened.
+    pub fn checksum(&self) -> u64 {
+        let mut hasher = StateHasher::new();
+        self.game_state.hash(&mut hasher);
+        hasher.write_u64(self.ticks);
+        hasher.write_usize(self.level);
+        hasher.write_u32(self.lives);
+        hasher.write_u32(self.scoring.score());
+        hasher.write_u32(self.scoring.combo());
+        hasher.write_f32(self.time);
+        hasher.write_vec2(self.player.get_position());
+        hasher.write_vec2(self.player.get_size());
+        for ball in self.balls.iter() {
+            hasher.write_vec2(ball.get_position());
+            hasher.write_vec2(ball.get_velocity());
+            ball.is_stuck().hash(&mut hasher);
+        }
+        if let Some(level) = self.current_level() {
+            for brick in level.bricks() {
+                brick.is_destroyed().hash(&mut hasher);
+                hasher.write_u32(brick.hit_points());
+            }
+        }
+        for power_up in self.power_ups.iter() {
+            power_up.kind().hash(&mut hasher);
+            hasher.write_vec2(power_up.game_object().get_position());
+        }
+        for active in self.active_power_ups.iter() {
+            active.kind.hash(&mut hasher);
+            hasher.write_f32(active.remaining);
+        }
+        self.name_entry.hash(&mut hasher);
+        // the next random number stands for the state of the generator
+        hasher.write_u64(self.rng.clone().gen());
+        hasher.finish()
+    }
+
+    /// chance that a destroyed brick drops a power-up, overrides the value of
+    /// the level files, `None` goes back to those
+    pub fn set_power_up_chance(&mut self, chance : Option<f32>) {
+        self.power_up_chance = chance;
+    }
+
+    /// the level being played or selected in the menu
+    pub fn current_level(&self) -> Option<&GameLevel> {
+        self.game_levels.get(self.level)
+    }
+
+    fn is_level_complete(&self) -> bool {
+        match self.current_level() {
+            Some(level) => level.is_complete(),
+            None => false,
+        }
+    }
+
+    /// the last ball left through the bottom of the screen, when no lives
+    /// are left the game is over
+    fn lose_life(&mut self) {
+        self.events.push(GameEvent::BallLost);
+        self.scoring.break_combo();
+        self.lives = self.lives.saturating_sub(1);
+        if self.lives == 0 {
+            self.set_state(GameState::GameOver);
+        } else {
+            self.reset_player();
+        }
+    }
+
+    /// add the time bonus and remember the time when it is the fastest
+    fn level_cleared(&mut self) {
+        let par_time = self.current_level().and_then(|level| level.header().par_time);
+        let bonus = self.scoring.level_cleared(par_time);
+        let level = self.level_name();
+        self.new_best_time = self.high_scores.record_time(&level, self.scoring.level_time());
+        log::info!("Level '{}' cleared in {:.1}s, time bonus {}", level, self.scoring.level_time(), bonus);
+        if self.new_best_time {
+            self.save_high_scores();
+        }
+    }
+
+    /// ask for a name when the score made it into the high score table
+    fn start_name_entry(&mut self) {
+        self.name_entry = if self.high_scores.qualifies(self.scoring.score()) {
+            Some(String::new())
+        } else {
+            None
+        };
+    }
+
+    fn save_high_scores(&self) {
+        if let Some(path) = &self.high_score_path {
+            if let Err(err) = self.high_scores.save(path) {
+                eprintln!("Error: {:#}", anyhow::Error::new(err));
+            }
+        }
+    }
+
+    /// reload the bricks of the current level from its file, when that
+    /// fails the level keeps its current bricks
+    fn reset_level(&mut self) {
+        self.particles.clear();
+        if let Some(level) = self.game_levels.get_mut(self.level) {
+            if let Err(err) = level.reload(&self.assets, &mut self.sprites) {
+                eprintln!("Error: {:#}", anyhow::Error::new(err));
+            }
+        }
+    }
+
+    /// paddle size of the current level
+    fn player_size(&self) -> glm::Vec2 {
+        match self.current_level().and_then(|level| level.header().paddle_width) {
+            Some(width) => glm::vec2(width, PLAYER_SIZE.y),
+            None => PLAYER_SIZE,
+        }
+    }
+
+    /// velocity of the ball when it leaves the paddle in the current level
+    fn initial_ball_velocity(&self) -> glm::Vec2 {
+        match self.current_level().and_then(|level| level.header().ball_speed) {
+            Some(speed) => INITIAL_BALL_VELOCITY.normalize() * speed,
+            None => INITIAL_BALL_VELOCITY,
+        }
+    }
+
+    /// put the paddle back in the middle with the ball stuck on top of it,
+    /// this ends all power-ups
+    fn reset_player(&mut self) {
+        self.clear_power_ups();
+        self.paddle_target = None;
+
+        let player_size = self.player_size();
+        let player_position = glm::vec2(
+            (self.width as f32 - player_size.x) / 2.0,
+            self.height as f32 - player_size.y );
+        self.player.set_size(player_size);
+        self.player.set_position(player_position);
+        self.player.save_position();
+
+        let ball_position = player_position +
+            glm::vec2(player_size.x / 2.0 - BALL_RADIUS, -BALL_RADIUS * 2.0);
+        let velocity = self.initial_ball_velocity();
+        self.balls.truncate(1);
+        self.balls[0].reset(ball_position, velocity);
+    }
+
+    fn do_collisions(&mut self)
+    {
+        let mut hits = Vec::new();
+        if let Some(level) = self.game_levels.get_mut(self.level) {
+            for ball in self.balls.iter_mut() {
+                hits.extend(level.update_ball_collisions(ball));
+            }
+        }
+        for hit in hits.iter() {
+            self.events.push(GameEvent::BrickHit { solid : hit.solid, destroyed : hit.destroyed });
+            if hit.solid {
+                self.shake_time = SHAKE_DURATION;
+            }
+            if !hit.solid {
+                self.scoring.brick_hit(hit.score, hit.destroyed);
+            }
+            if hit.destroyed {
+                self.particles.emit_burst(hit.position, hit.size, hit.color);
+                self.spawn_power_up(hit.position + hit.size.scale(0.5));
+            }
+        }
+
+        // check collisions for the player paddle (unless the ball is stuck)
+        let base_velocity = self.initial_ball_velocity();
+        for ball in self.balls.iter_mut() {
+            if !ball.is_stuck() && ball.check_collision(&self.player).is_some() {
+                ball.bounce_off_paddle(&self.player, base_velocity.x, PADDLE_BOUNCE_STRENGTH);
+                self.events.push(GameEvent::PaddleHit);
+                self.scoring.break_combo();
+            }
+        }
+    }
+
+    /// drop a random power-up from `position`, with the power-up chance of
+    /// the current level
+    fn spawn_power_up(&mut self, position : glm::Vec2) {
+        let chance = self.power_up_chance
+            .or_else(|| self.current_level().and_then(|level| level.header().power_up_chance))
+            .unwrap_or(POWER_UP_CHANCE);
+        if self.rng.gen::<f32>() < chance {
+            let kind = PowerUpKind::ALL[self.rng.gen_range(0..PowerUpKind::ALL.len())];
+            self.power_ups.push(PowerUp::new(kind, position, self.power_up_sprite));
+        }
+    }
+
+    /// move the pickups, activate the ones the paddle catches and end the
+    /// effects that ran out
+    fn update_power_ups(&mut self, dt : f32) {
+        let player = &self.player;
+        let height = self.height as f32;
+        let mut caught = Vec::new();
+        self.power_ups.retain_mut(|power_up| {
+            power_up.save_position();
+            power_up.do_move(dt);
+            if power_up.game_object().check_collision(player) {
+                caught.push(power_up.kind());
+                return false;
+            }
+            power_up.game_object().get_position().y < height
+        });
+        for kind in caught {
+            self.activate_power_up(kind);
+        }
+
+        let mut expired = Vec::new();
+        self.active_power_ups.retain_mut(|active| {
+            active.remaining -= dt;
+            if active.remaining <= 0.0 {
+                expired.push(active.kind);
+                return false;
+            }
+            true
+        });
+        for kind in expired {
+            // another pickup of the same kind keeps the effect going
+            if !self.is_power_up_active(kind) {
+                self.apply_power_up(kind, false);
+            }
+        }
+    }
+
+    fn is_power_up_active(&self, kind : PowerUpKind) -> bool {
+        self.active_power_ups.iter().any(|active| active.kind == kind)
+    }
+
+    fn activate_power_up(&mut self, kind : PowerUpKind) {
+        self.events.push(GameEvent::PowerUpCollected(kind));
+        if !self.is_power_up_active(kind) {
+            self.apply_power_up(kind, true);
+        }
+        if kind.duration() > 0.0 {
+            self.active_power_ups.push(ActivePowerUp {
+                kind,
+                remaining : kind.duration(),
+            });
+        }
+    }
+
+    /// start or revert the effect of a power-up
+    fn apply_power_up(&mut self, kind : PowerUpKind, enable : bool) {
+        match kind {
+            PowerUpKind::Speed => {
+                let factor = if enable { SPEED_UP_FACTOR } else { 1.0 / SPEED_UP_FACTOR };
+                self.balls.iter_mut().for_each(|ball| ball.scale_velocity(factor));
+            },
+            PowerUpKind::Sticky => {
+                self.balls.iter_mut().for_each(|ball| ball.set_sticky(enable));
+                let color = if enable { kind.color() } else { glm::vec3(1.0, 1.0, 1.0) };
+                self.player.set_color(color);
+            },
+            PowerUpKind::PassThrough => {
+                let color = if enable { kind.color() } else { glm::vec3(1.0, 1.0, 1.0) };
+                self.balls.iter_mut().for_each(|ball| {
+                    ball.set_pass_through(enable);
+                    ball.set_color(color);
+                });
+            },
+            PowerUpKind::PaddleGrow => {
+                // grow or shrink around the centre of the paddle
+                let grow = if enable { PADDLE_GROW_WIDTH } else { -PADDLE_GROW_WIDTH };
+                let size = self.player.get_size() + glm::vec2(grow, 0.0);
+                let position = self.player.get_position() - glm::vec2(grow / 2.0, 0.0);
+                self.player.set_size(size);
+                self.player.set_position(position);
+            },
+            PowerUpKind::Confuse => self.confuse = enable,
+            PowerUpKind::Chaos => self.chaos = enable,
+            PowerUpKind::MultiBall => {
+                if enable {
+                    self.split_ball(MULTI_BALL_COUNT);
+                }
+            },
+        }
+    }
+
+    /// remove all pickups and end every effect without reverting it, used
+    /// when the paddle and ball are reset anyway
+    fn clear_power_ups(&mut self) {
+        self.power_ups.clear();
+        self.active_power_ups.clear();
+        self.confuse = false;
+        self.chaos = false;
+        self.player.set_color(glm::vec3(1.0, 1.0, 1.0));
+        self.balls.iter_mut().for_each(|ball| ball.set_color(glm::vec3(1.0, 1.0, 1.0)));
+    }
+
+    /// split a ball into `count` balls that fan out from its direction, a
+    /// moving ball is preferred over one stuck to the paddle
+    pub fn split_ball(&mut self, count : usize) {
+        let index = self.balls.iter().position(|ball| !ball.is_stuck()).unwrap_or(0);
+        let source = match self.balls.get_mut(index) {
+            Some(ball) => {
+                ball.un_stuck();
+                ball.clone()
+            },
+            None => return,
+        };
+
+        for split in 1..count {
+            // alternate left and right of the original direction
+            let step = split.div_ceil(2) as f32 * MULTI_BALL_SPREAD;
+            let angle = if split % 2 == 1 { step } else { -step };
+            let mut ball = source.clone();
+            ball.set_velocity(glm::rotate_vec2(&source.get_velocity(), angle.to_radians()));
+            self.balls.push(ball);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_level::Brick;
+
+    const STEP : f32 = 1.0 / 120.0;
+
+    fn new_game() -> Game {
+        let mut game = Game::new(800, 600);
+        game.init().unwrap();
+        game
+    }
+
+    fn press(game : &mut Game, key : VirtualKeyCode) {
+        game.process_key(key, ElementState::Pressed);
+        game.process_input();
+        game.process_key(key, ElementState::Released);
+    }
+
+    /// put the first ball in play with its centre at `center`
+    fn place_ball(game : &mut Game, center : glm::Vec2, velocity : glm::Vec2) {
+        let ball = &mut game.balls[0];
+        ball.reset(center - glm::vec2(BALL_RADIUS, BALL_RADIUS), velocity);
+        ball.un_stuck();
+    }
+
+    /// the index and centre of the first brick of the current level that
+    /// matches
+    fn find_brick(game : &Game, matches : impl Fn(&Brick) -> bool) -> (usize, glm::Vec2) {
+        let bricks = game.current_level().unwrap().bricks();
+        let index = bricks.iter().position(matches).unwrap();
+        let object = bricks[index].object();
+        (index, object.get_position() + object.get_size().scale(0.5))
+    }
+
+    /// destroy every brick of the current level with a ball placed on it
+    fn clear_level(game : &mut Game) {
+        let sprite = game.balls()[0].game_object().get_sprite();
+        let level = &mut game.game_levels[game.level];
+        while level.remaining_bricks() > 0 {
+            let centers : Vec<glm::Vec2> = level.bricks().iter()
+                .filter(|brick| brick.is_destroyable() && !brick.is_destroyed())
+                .map(|brick| brick.object().get_position() + brick.object().get_size().scale(0.5))
+                .collect();
+            for center in centers {
+                let position = center - glm::vec2(BALL_RADIUS, BALL_RADIUS);
+                let mut ball = BallObject::new(position, BALL_RADIUS, INITIAL_BALL_VELOCITY, sprite);
+                ball.un_stuck();
+                level.update_ball_collisions(&mut ball);
+            }
+        }
+    }
+
+    #[test]
+    fn enter_starts_the_selected_level() {
+        let mut game = new_game();
+        assert_eq!(game.state(), GameState::GameMenu);
+
+        press(&mut game, VirtualKeyCode::Return);
+        assert_eq!(game.state(), GameState::GameActive);
+        assert_eq!(game.lives(), INITIAL_LIVES);
+        assert_eq!(game.score(), 0);
+        assert!(game.take_events().contains(&GameEvent::StateChanged(GameState::GameActive)));
+    }
+
+    #[test]
+    fn space_launches_the_ball() {
+        let mut game = new_game();
+        press(&mut game, VirtualKeyCode::Return);
+
+        // the ball waits on the paddle
+        let start = game.balls()[0].get_position();
+        for _ in 0..10 {
+            game.update(STEP);
+        }
+        assert!(game.balls()[0].is_stuck());
+        assert_eq!(game.balls()[0].get_position(), start);
+
+        press(&mut game, VirtualKeyCode::Space);
+        for _ in 0..10 {
+            game.update(STEP);
+        }
+        let ball = &game.balls()[0];
+        assert!(!ball.is_stuck());
+        assert_eq!(ball.get_velocity(), INITIAL_BALL_VELOCITY);
+        assert!(ball.get_position().y < start.y);
+    }
+
+    #[test]
+    fn destroying_a_brick_scores() {
+        let mut game = new_game();
+        game.set_power_up_chance(Some(0.0));
+        press(&mut game, VirtualKeyCode::Return);
+        press(&mut game, VirtualKeyCode::Space);
+        let bricks = game.current_level().unwrap().remaining_bricks();
+        game.take_events();
+
+        // the ball reaches the bricks within a few seconds
+        let mut events = Vec::new();
+        for _ in 0..600 {
+            game.update(STEP);
+            events.extend(game.take_events());
+            if game.score() > 0 {
+                break;
+            }
+        }
+        assert!(game.score() > 0);
+        assert!(events.contains(&GameEvent::BrickHit { solid : false, destroyed : true }));
+        assert_eq!(game.current_level().unwrap().remaining_bricks(), bricks - 1);
+    }
+
+    #[test]
+    fn cleared_level_is_complete_when_played_again() {
+        let mut game = new_game();
+        press(&mut game, VirtualKeyCode::Return);
+        let bricks = game.current_level().unwrap().remaining_bricks();
+        clear_level(&mut game);
+        game.update(STEP);
+        assert_eq!(game.state(), GameState::GameWin);
+
+        // the time bonus is a high score, enter the name and go on to the
+        // menu, which selected the next level, then back to the first one
+        assert!(game.name_entry().is_some());
+        press(&mut game, VirtualKeyCode::Return);
+        press(&mut game, VirtualKeyCode::Return);
+        assert_eq!(game.state(), GameState::GameMenu);
+        assert_eq!(game.level, 1);
+        press(&mut game, VirtualKeyCode::Down);
+        press(&mut game, VirtualKeyCode::Return);
+        assert_eq!(game.state(), GameState::GameActive);
+        assert_eq!(game.current_level().unwrap().remaining_bricks(), bricks);
+
+        game.update(STEP);
+        assert_eq!(game.state(), GameState::GameActive);
+    }
+
+    #[test]
+    fn losing_the_last_ball_costs_a_life() {
+        let mut game = new_game();
+        press(&mut game, VirtualKeyCode::Return);
+        game.take_events();
+
+        place_ball(&mut game, glm::vec2(400.0, 620.0), INITIAL_BALL_VELOCITY);
+        game.update(STEP);
+        assert_eq!(game.lives(), INITIAL_LIVES - 1);
+        assert_eq!(game.state(), GameState::GameActive);
+        assert!(game.take_events().contains(&GameEvent::BallLost));
+        // the ball waits on the paddle again
+        let ball = &game.balls()[0];
+        assert!(ball.is_stuck());
+        assert!(ball.get_position().y < game.player().get_position().y);
+    }
+
+    #[test]
+    fn losing_all_lives_ends_the_game() {
+        let mut game = new_game();
+        press(&mut game, VirtualKeyCode::Return);
+        for _ in 0..INITIAL_LIVES {
+            assert_eq!(game.state(), GameState::GameActive);
+            place_ball(&mut game, glm::vec2(400.0, 620.0), INITIAL_BALL_VELOCITY);
+            game.update(STEP);
+        }
+        assert_eq!(game.lives(), 0);
+        assert_eq!(game.state(), GameState::GameOver);
+
+        // the next game starts with all lives
+        press(&mut game, VirtualKeyCode::Return);
+        press(&mut game, VirtualKeyCode::Return);
+        assert_eq!(game.state(), GameState::GameActive);
+        assert_eq!(game.lives(), INITIAL_LIVES);
+    }
+
+    #[test]
+    fn paddle_bounce_angle_follows_the_hit_position() {
+        let mut game = new_game();
+        press(&mut game, VirtualKeyCode::Return);
+        let paddle = game.player().get_position();
+        let paddle_width = game.player().get_size().x;
+        let speed = INITIAL_BALL_VELOCITY.norm();
+
+        let mut bounce = |offset : f32| {
+            let center = glm::vec2(paddle.x + paddle_width * offset, paddle.y - BALL_RADIUS + 2.0);
+            place_ball(&mut game, center, glm::vec2(0.0, speed));
+            game.update(STEP);
+            assert!(game.take_events().contains(&GameEvent::PaddleHit));
+            game.balls()[0].get_velocity()
+        };
+
+        let left = bounce(0.1);
+        let middle = bounce(0.5);
+        let right = bounce(0.9);
+        for velocity in [left, middle, right] {
+            assert!(velocity.y < 0.0);
+            assert!((velocity.norm() - speed).abs() < 0.01);
+        }
+        assert!(left.x < 0.0 && right.x > 0.0);
+        assert!(middle.x.abs() < 1.0);
+        assert!((left.x + right.x).abs() < 0.01);
+    }
+
+    #[test]
+    fn multi_hit_brick_is_damaged_before_it_breaks() {
+        let mut game = new_game();
+        game.set_power_up_chance(Some(0.0));
+        // the toml level, one step down from the first, has three hit bricks
+        press(&mut game, VirtualKeyCode::Down);
+        press(&mut game, VirtualKeyCode::Return);
+        let (index, center) = find_brick(&game, |brick| brick.kind().hit_points == 3);
+        let color = game.current_level().unwrap().bricks()[index].object().get_color();
+        game.take_events();
+
+        for hit_points in [2, 1] {
+            place_ball(&mut game, center, glm::vec2(0.0, -100.0));
+            game.update(STEP);
+            let brick = &game.current_level().unwrap().bricks()[index];
+            assert_eq!(brick.hit_points(), hit_points);
+            assert!(!brick.is_destroyed());
+            // damaged bricks get darker
+            assert!(brick.object().get_color().x < color.x);
+            assert!(game.take_events().contains(&GameEvent::BrickHit { solid : false, destroyed : false }));
+            assert_eq!(game.score(), 0);
+        }
+
+        place_ball(&mut game, center, glm::vec2(0.0, -100.0));
+        game.update(STEP);
+        assert!(game.current_level().unwrap().bricks()[index].is_destroyed());
+        assert!(game.take_events().contains(&GameEvent::BrickHit { solid : false, destroyed : true }));
+        assert!(game.score() >= 50);
+    }
+
+    #[test]
+    fn destroyed_brick_drops_a_power_up() {
+        let mut game = new_game();
+        game.set_power_up_chance(Some(1.0));
+        press(&mut game, VirtualKeyCode::Return);
+        // the first brick of the bottom row, nothing is below it
+        let bottom = game.current_level().unwrap().bricks().last().unwrap().object().get_position().y;
+        let (index, center) = find_brick(&game, |brick| brick.object().get_position().y == bottom);
+
+        place_ball(&mut game, glm::vec2(center.x, center.y + 30.0), glm::vec2(0.0, -100.0));
+        game.update(STEP);
+        assert!(game.current_level().unwrap().bricks()[index].is_destroyed());
+        assert_eq!(game.power_ups().len(), 1);
+        // it falls from the brick
+        let power_up = game.power_ups()[0].game_object();
+        assert!(power_up.get_position().y < center.y);
+        assert!(power_up.get_velocity().y > 0.0);
+    }
+
+    #[test]
+    fn caught_power_up_lasts_its_duration() {
+        let mut game = new_game();
+        press(&mut game, VirtualKeyCode::Return);
+        let paddle_width = game.player().get_size().x;
+        let paddle_center = game.player().get_position() + game.player().get_size().scale(0.5);
+        let kind = PowerUpKind::PaddleGrow;
+        game.power_ups.push(PowerUp::new(kind, paddle_center - glm::vec2(0.0, 40.0), game.power_up_sprite));
+        game.take_events();
+
+        // the ball stays on the paddle, nothing else happens meanwhile
+        for _ in 0..60 {
+            game.update(STEP);
+        }
+        assert!(game.power_ups().is_empty());
+        assert!(game.take_events().contains(&GameEvent::PowerUpCollected(kind)));
+        assert_eq!(game.player().get_size().x, paddle_width + PADDLE_GROW_WIDTH);
+        assert_eq!(game.active_power_ups().len(), 1);
+
+        let ticks = (kind.duration() / STEP) as usize;
+        for _ in 0..ticks {
+            game.update(STEP);
+        }
+        assert!(game.active_power_ups().is_empty());
+        assert_eq!(game.player().get_size().x, paddle_width);
+        // the paddle shrank around its centre
+        let center = game.player().get_position() + game.player().get_size().scale(0.5);
+        assert!((center.x - paddle_center.x).abs() < 0.01);
+    }
+
+    #[test]
+    fn pass_through_ball_only_passes_bricks_it_destroys() {
+        let mut game = new_game();
+        game.set_power_up_chance(Some(0.0));
+        // the toml level, hit the bricks from below where nothing else is
+        press(&mut game, VirtualKeyCode::Down);
+        press(&mut game, VirtualKeyCode::Return);
+        let velocity = glm::vec2(0.0, -100.0);
+        let hit_from_below = |game : &mut Game, center : glm::Vec2| {
+            place_ball(game, glm::vec2(center.x, center.y + 35.0), velocity);
+            game.balls[0].set_pass_through(true);
+            game.take_events();
+            game.update(STEP);
+            game.balls()[0].get_velocity()
+        };
+
+        // a breakable brick of the third row, above the empty middle
+        let (_, center) = find_brick(&game, |brick| brick.kind().score == 10 && brick.object().get_position().x > 150.0);
+        assert_eq!(hit_from_below(&mut game, center), velocity);
+        assert!(game.take_events().contains(&GameEvent::BrickHit { solid : false, destroyed : true }));
+
+        // a solid brick of the bottom row
+        let bottom = game.current_level().unwrap().bricks().last().unwrap().object().get_position().y;
+        let (_, center) = find_brick(&game, |brick| brick.is_solid() && brick.object().get_position().y == bottom);
+        assert_eq!(hit_from_below(&mut game, center), -velocity);
+        assert!(game.take_events().contains(&GameEvent::BrickHit { solid : true, destroyed : false }));
+    }
+
+    #[test]
+    fn only_losing_the_last_ball_costs_a_life() {
+        let mut game = new_game();
+        press(&mut game, VirtualKeyCode::Return);
+        press(&mut game, VirtualKeyCode::Space);
+        game.split_ball(3);
+        assert_eq!(game.balls().len(), 3);
+        game.take_events();
+
+        // two of the balls leave through the bottom
+        for ball in game.balls[1..].iter_mut() {
+            ball.reset(glm::vec2(400.0, 620.0), INITIAL_BALL_VELOCITY);
+            ball.un_stuck();
+        }
+        game.update(STEP);
+        assert_eq!(game.balls().len(), 1);
+        assert_eq!(game.lives(), INITIAL_LIVES);
+        assert!(!game.take_events().contains(&GameEvent::BallLost));
+
+        place_ball(&mut game, glm::vec2(400.0, 620.0), INITIAL_BALL_VELOCITY);
+        game.update(STEP);
+        assert_eq!(game.balls().len(), 1);
+        assert_eq!(game.lives(), INITIAL_LIVES - 1);
+        assert!(game.take_events().contains(&GameEvent::BallLost));
+    }
+}
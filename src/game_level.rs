@@ -1,112 +1,245 @@
-use std::rc::Rc;
-use crate::game_object::GameObject;
-use crate::ball_object::BallObject;
-use crate::sprite_renderer::SpriteRenderer;
-use crate::resource_manager::ResourceManager;
-
-pub struct GameLevel
-{
-    resource_manager : Rc<ResourceManager>,
-    bricks : Vec<GameObject>,
-}
-
-impl GameLevel {
-    pub fn new(resource_manager : Rc<ResourceManager>) ->Self {
-        Self {
-            resource_manager,
-            bricks : Vec::new(),
-        }
-    }
-
-    pub fn load(&mut self, level_path : &str, level_width:u32, level_height:u32) {
-
-        self.bricks.clear();
-
-        let mut tile_data = Vec::new();
-
-        let content = std::fs::read_to_string(level_path).expect(format!("Failed to read file: {}",level_path).as_str());
-        for line in content.lines() {
-            
-            let row : Vec<u32> = line.split_ascii_whitespace().map(|s| 
-                u32::from_str_radix(s, 10).unwrap() ).collect();
-
-            println!("LINE: {:?}", &row);
-            tile_data.push(row);
-        }
-        self.create_bricks(tile_data,level_width, level_height);
-    }
-
-    fn create_bricks(&mut self, tile_data: Vec<Vec<u32>>, level_width:u32, level_height:u32) {
-        
-        let height  = tile_data.len();
-        let width = tile_data[0].len();
-   
-        let unit_width = level_width as f32 / width as f32;
-        let unit_height = level_height as f32 / height as f32;
-        
-        // initialize level tiles based on tileData		
-        for y in 0..height {
-            for x in 0..width {
-                let color;
-                let mut solid = false;
-
-                match tile_data[y][x] {
-                    1 => {
-                        color = glm::vec3(0.8, 0.8, 0.7);
-                        solid = true;
-                    },
-                    2 => color = glm::vec3(0.2,0.6,1.0),
-                    3 => color = glm::vec3(0.0,0.7,0.0),
-                    4 => color = glm::vec3(0.8,0.8,0.4), 
-                    5 => color = glm::vec3(1.0,0.5,0.0),                   
-                    _level => {
-                        //println!("SKIP {} {} {}", x, y, level);
-                        continue;
-                    }
-                }
-                let position = glm::vec2(unit_width * x as f32, unit_height * y as f32);
-                let size = glm::vec2 (unit_width as f32, unit_height as f32);
-                let texture = if solid {
-                    self.resource_manager.load_texture("resources/textures/block_solid.png", "block_solid".into())
-                } else {
-                    self.resource_manager.load_texture("resources/textures/block.png", "block".into())
-                };
-
-                let brick = GameObject::new(
-                    position, 
-                    size,
-                    glm::vec2(0.0f32,0.0f32),
-                    color,
-                    0.0,
-                    texture, 
-                    solid);
-                
-                self.bricks.push(brick);
-
-            }
-        }
-    }
-
-    pub fn draw(&self, renderer: &SpriteRenderer) {
-        self.bricks.iter().for_each(|tile| {
-            if !tile.is_destroyed() {
-                tile.draw(renderer);
-            }
-        }); 
-    }
-
-    pub fn is_complete(&self) ->bool {
-        self.bricks.iter().any( |tile| {
-            !tile.is_solid() && !tile.is_destroyed()
-        })
-    }
-
-    pub fn update_ball_collisions(&mut self,  ball : &BallObject) {
-        //println!("Check with : {:?}", ball);
-        self.bricks.iter_mut().for_each(|brick| {
-            if ball.check_collision(brick) && !brick.is_solid() {
-                brick.set_destroyed();
-            }
-        });
-    }
-}
+use std::collections::HashMap;
+use crate::game_object::GameObject;
+use crate::ball_object::BallObject;
+use crate::sprite::{SpriteId, Sprites};
+use crate::assets::Assets;
+use crate::error::ResourceError;
+use crate::level_data::{BrickKind, LevelData, LevelHeader};
+
+// brightness of a brick with one hit point left, relative to an undamaged one
+const DAMAGED_BRIGHTNESS : f32 = 0.5;
+
+/// A brick of the level, with the palette entry it was made from
+#[derive(Debug)]
+pub struct Brick {
+    object : GameObject,
+    kind : BrickKind,
+    hit_points : u32,
+    damage_sprites : Vec<SpriteId>,
+}
+
+impl Brick {
+    /// hits left before the brick is destroyed
+    pub fn hit_points(&self) -> u32 {
+        self.hit_points
+    }
+
+    /// bricks that are not solid can be destroyed
+    pub fn is_destroyable(&self) -> bool {
+        !self.is_solid()
+    }
+
+    /// take one hit point off and show the damage, returns true when this
+    /// hit destroyed the brick
+    fn hit(&mut self) -> bool {
+        if !self.is_destroyable() || self.is_destroyed() {
+            return false;
+        }
+        self.hit_points = self.hit_points.saturating_sub(1);
+        if self.hit_points == 0 {
+            self.object.set_destroyed();
+            return true;
+        }
+
+        let hits_taken = (self.kind.hit_points - self.hit_points) as usize;
+        if let Some(sprite) = self.damage_sprites.get(hits_taken - 1).or(self.damage_sprites.last()) {
+            self.object.set_sprite(*sprite);
+        }
+        // fade from the full colour towards DAMAGED_BRIGHTNESS on the last hit point
+        let health = (self.hit_points - 1) as f32 / (self.kind.hit_points - 1) as f32;
+        let brightness = DAMAGED_BRIGHTNESS + (1.0 - DAMAGED_BRIGHTNESS) * health;
+        self.object.set_color(glm::make_vec3(&self.kind.color) * brightness);
+        false
+    }
+
+    pub fn object(&self) -> &GameObject {
+        &self.object
+    }
+
+    pub fn kind(&self) -> &BrickKind {
+        &self.kind
+    }
+
+    pub fn is_destroyed(&self) -> bool {
+        self.object.is_destroyed()
+    }
+
+    pub fn is_solid(&self) -> bool {
+        self.object.is_solid()
+    }
+}
+
+/// A brick the ball touched during an update
+#[derive(Debug, Clone, Copy)]
+pub struct BrickHit {
+    pub position : glm::Vec2,
+    pub size : glm::Vec2,
+    pub color : glm::Vec3,
+    pub solid : bool,
+    /// the hit took the last hit point of the brick
+    pub destroyed : bool,
+    /// points of the brick when the hit destroyed it, before the combo
+    /// multiplier
+    pub score : u32,
+}
+
+pub struct GameLevel
+{
+    header : LevelHeader,
+    background_sprite : Option<SpriteId>,
+    bricks : Vec<Brick>,
+    level_path : String,
+    level_width : u32,
+    level_height : u32,
+}
+
+impl Default for GameLevel {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl GameLevel {
+    pub fn new() ->Self {
+        Self {
+            header : LevelHeader::default(),
+            background_sprite : None,
+            bricks : Vec::new(),
+            level_path : String::new(),
+            level_width : 0,
+            level_height : 0,
+        }
+    }
+
+    /// load a level file, the textures it uses are added to `sprites`
+    pub fn load(&mut self, assets : &Assets, level_path : &str, level_width:u32, level_height:u32, sprites : &mut Sprites) -> Result<(), ResourceError> {
+
+        self.level_path = level_path.into();
+        self.level_width = level_width;
+        self.level_height = level_height;
+
+        let level_data = LevelData::load(assets, level_path)?;
+
+        self.background_sprite = level_data.header.background.as_ref()
+            .map(|background| sprites.register(background, background));
+        self.bricks.clear();
+        self.create_bricks(&level_data, level_width, level_height, sprites);
+        self.header = level_data.header;
+        Ok(())
+    }
+
+    /// restore all bricks by loading the level file again
+    pub fn reload(&mut self, assets : &Assets, sprites : &mut Sprites) -> Result<(), ResourceError> {
+        let level_path = self.level_path.clone();
+        self.load(assets, &level_path, self.level_width, self.level_height, sprites)
+    }
+
+    fn create_bricks(&mut self, level_data: &LevelData, level_width:u32, level_height:u32, sprites : &mut Sprites) {
+        
+        let tile_data = &level_data.grid;
+        let height  = tile_data.len();
+        let width = tile_data[0].len();
+   
+        let unit_width = level_width as f32 / width as f32;
+        let unit_height = level_height as f32 / height as f32;
+
+        // one sprite for every texture in the palette
+        let kind_sprites : HashMap<u32, SpriteId> = level_data.palette.iter()
+            .map(|(id, kind)| (*id, sprites.register(kind.texture(), kind.texture())))
+            .collect();
+        let damage_sprites : HashMap<u32, Vec<SpriteId>> = level_data.palette.iter()
+            .map(|(id, kind)| (*id, kind.damage_textures.iter()
+                .map(|texture| sprites.register(texture, texture))
+                .collect()))
+            .collect();
+        
+        // initialize level tiles based on tileData		
+        for (y, row) in tile_data.iter().enumerate() {
+            for (x, tile) in row.iter().enumerate() {
+                let kind = match level_data.palette.get(tile) {
+                    Some(kind) => kind,
+                    None => continue,
+                };
+
+                let position = glm::vec2(unit_width * x as f32, unit_height * y as f32);
+                let size = glm::vec2 (unit_width, unit_height);
+
+                let object = GameObject::new(
+                    position, 
+                    size,
+                    glm::vec2(0.0f32,0.0f32),
+                    glm::make_vec3(&kind.color),
+                    0.0,
+                    kind_sprites[tile], 
+                    kind.solid);
+                
+                self.bricks.push(Brick {
+                    object,
+                    kind : kind.clone(),
+                    hit_points : kind.hit_points,
+                    damage_sprites : damage_sprites[tile].clone(),
+                });
+
+            }
+        }
+    }
+
+    /// the file the level was loaded from
+    pub fn path(&self) -> &str {
+        &self.level_path
+    }
+
+    pub fn header(&self) -> &LevelHeader {
+        &self.header
+    }
+
+    pub fn background_sprite(&self) -> Option<SpriteId> {
+        self.background_sprite
+    }
+
+    pub fn bricks(&self) -> &[Brick] {
+        &self.bricks
+    }
+
+    /// number of bricks that can still be destroyed
+    pub fn remaining_bricks(&self) -> usize {
+        self.bricks.iter()
+            .filter(|brick| brick.is_destroyable() && !brick.is_destroyed())
+            .count()
+    }
+
+    /// true when all breakable bricks are destroyed
+    pub fn is_complete(&self) ->bool {
+        self.remaining_bricks() == 0
+    }
+
+    /// Bounce the ball off every brick it touches, every touch costs a brick
+    /// one hit point and solid bricks are never destroyed. A pass-through
+    /// ball only goes through the bricks it destroys. Returns the bricks
+    /// that were hit.
+    pub fn update_ball_collisions(&mut self,  ball : &mut BallObject) -> Vec<BrickHit> {
+        //println!("Check with : {:?}", ball);
+        let mut hits = Vec::new();
+        self.bricks.iter_mut().for_each(|brick| {
+            if brick.is_destroyed() {
+                return;
+            }
+            if let Some(collision) = ball.check_collision(&brick.object) {
+                let color = brick.object.get_color();
+                let destroyed = brick.hit();
+                hits.push(BrickHit {
+                    position : brick.object.get_position(),
+                    size : brick.object.get_size(),
+                    color,
+                    solid : brick.is_solid(),
+                    destroyed,
+                    score : if destroyed { brick.kind.score } else { 0 },
+                });
+                if brick.is_solid() || !destroyed || !ball.is_pass_through() {
+                    ball.resolve_collision(&collision);
+                }
+            }
+        });
+        hits
+    }
+}
@@ -0,0 +1,396 @@
+use glow::HasContext;
+use std::path::Path;
+use std::rc::Rc;
+use std::time::Instant;
+use crate::error::ResourceError;
+use crate::game::{Game, GameState};
+use crate::game_object::GameObject;
+use crate::post_processor::PostProcessor;
+use crate::resource_manager::ResourceManager;
+use crate::shader::Shader;
+use crate::sprite::SpriteId;
+use crate::sprite_renderer::{SpriteQuad, SpriteRenderer};
+use crate::text_renderer::TextRenderer;
+use crate::texture_atlas::{TextureAtlas, TextureRegion};
+use crate::viewport::Viewport;
+use crate::screenshot;
+
+extern crate nalgebra_glm as glm;
+
+// sprites up to this width and height are packed into the sprite atlas
+const ATLAS_MAX_SPRITE_SIZE : u32 = 512;
+// samples per pixel of the offscreen scene
+const MULTISAMPLE_SAMPLES : u32 = 4;
+// font of the HUD and menus, with a bitmap font when it can not be loaded
+const FONT_FILE : &str = "resources/fonts/DejaVuSansMono-Bold.ttf";
+const FONT_SIZE : f32 = 24.0;
+const BITMAP_FONT_FILE : &str = "resources/fonts/ascii_16x24.png";
+const BITMAP_FONT_CELL : (u32, u32) = (16, 24);
+
+/// Draws the state of a `Game`, owns all GL resources needed for that and
+/// maps the sprite handles of the simulation onto textures
+pub struct GameRenderer {
+    resource_manager : Rc<ResourceManager>,
+    sprite_renderer : SpriteRenderer,
+    post_processor : PostProcessor,
+    text_renderer : TextRenderer,
+    textures : Vec<Option<TextureRegion>>,
+    atlas : Option<Rc<TextureAtlas>>,
+    viewport : Viewport,
+    show_fps : bool,
+    fps : f32,
+    fps_frames : u32,
+    fps_start : Instant,
+}
+
+impl GameRenderer {
+    /// a renderer for a logical playfield of `width` by `height`, which is
+    /// scaled to the window size given to `resize`
+    pub fn new(resource_manager : Rc<ResourceManager>, width : u32, height : u32) -> Result<Self, ResourceError> {
+        let sprite_shader = resource_manager.load_shader(
+            "src/shaders/sprite.vs",
+            "src/shaders/sprite.fs",
+            "sprite".into()
+        )?;
+
+        set_sprite_uniforms(&sprite_shader, width, height);
+
+        let sprite_renderer = SpriteRenderer::new(
+            resource_manager.get_gl(),
+            sprite_shader,
+        );
+
+        let post_processor = PostProcessor::new(&resource_manager, width, height, MULTISAMPLE_SAMPLES)?;
+
+        let font = match resource_manager.load_truetype_font(FONT_FILE, FONT_SIZE, "hud".into()) {
+            Ok(font) => font,
+            Err(err) => {
+                eprintln!("Error: {:#}", anyhow::Error::new(err));
+                let (cell_width, cell_height) = BITMAP_FONT_CELL;
+                resource_manager.load_bitmap_font(BITMAP_FONT_FILE, cell_width, cell_height, "hud".into())?
+            }
+        };
+
+        Ok(Self {
+            resource_manager,
+            sprite_renderer,
+            post_processor,
+            text_renderer : TextRenderer::new(font),
+            textures : Vec::new(),
+            atlas : None,
+            viewport : Viewport::new(width, height),
+            show_fps : false,
+            fps : 0.0,
+            fps_frames : 0,
+            fps_start : Instant::now(),
+        })
+    }
+
+    /// load the textures of sprites the game registered since the last call,
+    /// a sprite that fails to load is not drawn.
+    ///
+    /// The first call packs all small sprites into one atlas, sprites that
+    /// are too big or registered later get a texture of their own.
+    pub fn load_sprites(&mut self, game : &Game) -> Result<(), ResourceError> {
+        if self.atlas.is_none() {
+            self.atlas = Some(self.load_atlas(game)?);
+        }
+
+        for (_, entry) in game.sprites().iter().skip(self.textures.len()) {
+            let region = match self.atlas.as_ref().and_then(|atlas| atlas.region(&entry.name)) {
+                Some(region) => Ok(region),
+                None => self.resource_manager.load_texture(&entry.path, entry.name.clone())
+                    .map(TextureRegion::whole),
+            };
+            match region {
+                Ok(region) => self.textures.push(Some(region)),
+                Err(err) => {
+                    self.textures.push(None);
+                    return Err(err);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn load_atlas(&self, game : &Game) -> Result<Rc<TextureAtlas>, ResourceError> {
+        let images : Vec<(&str, &str)> = game.sprites().iter()
+            .filter(|(_, entry)| match self.resource_manager.image_dimensions(&entry.path) {
+                Ok((width, height)) => width <= ATLAS_MAX_SPRITE_SIZE && height <= ATLAS_MAX_SPRITE_SIZE,
+                Err(_) => false,
+            })
+            .map(|(_, entry)| (entry.name.as_str(), entry.path.as_str()))
+            .collect();
+        self.resource_manager.load_atlas(&images, "sprites".into())
+    }
+
+    /// write the sprite atlas and its layout to disk for inspection
+    pub fn dump_atlas(&self, path : &Path) -> Result<(), ResourceError> {
+        match &self.atlas {
+            Some(atlas) => atlas.dump(path),
+            None => Err(ResourceError::MissingAtlas("sprites".into())),
+        }
+    }
+
+    fn texture(&self, sprite : SpriteId) -> Option<&TextureRegion> {
+        self.textures.get(sprite.index()).and_then(|texture| texture.as_ref())
+    }
+
+    fn draw_object(&self, object : &GameObject, alpha : f32) {
+        if let Some(texture) = self.texture(object.get_sprite()) {
+            self.sprite_renderer.draw_region(
+                texture,
+                object.get_interpolated_position(alpha),
+                object.get_size(),
+                object.get_rotation(),
+                object.get_color());
+        }
+    }
+
+    /// the window now has this size in physical pixels
+    pub fn resize(&mut self, physical_width : u32, physical_height : u32) {
+        self.viewport.resize(physical_width, physical_height);
+    }
+
+    /// where the playfield is drawn in the window
+    pub fn viewport(&self) -> &Viewport {
+        &self.viewport
+    }
+
+    /// read back the playfield as it was last rendered, call it before the
+    /// buffers are swapped
+    pub fn capture(&self) -> image::RgbaImage {
+        let gl = self.resource_manager.get_gl();
+        let (x, y, width, height) = self.viewport.gl_rect();
+        unsafe { gl.bind_framebuffer(glow::READ_FRAMEBUFFER, None); }
+        screenshot::read_pixels(&gl, x, y, width, height)
+    }
+
+    /// load the shaders and textures made from any of the `changed` assets
+    /// again
+    pub fn reload_changed(&self, changed : &[String]) {
+        self.resource_manager.reload_changed(changed);
+        // a new program starts without uniforms
+        if let Ok(sprite_shader) = self.resource_manager.get_shader("sprite".into()) {
+            let (width, height) = self.viewport.logical_size();
+            set_sprite_uniforms(&sprite_shader, width, height);
+        }
+        self.post_processor.set_uniforms();
+    }
+
+    /// show or hide the frames per second counter
+    pub fn toggle_fps(&mut self) {
+        self.show_fps = !self.show_fps;
+    }
+
+    /// count a rendered frame, the rate is updated every second
+    fn count_frame(&mut self) {
+        self.fps_frames += 1;
+        let elapsed = self.fps_start.elapsed().as_secs_f32();
+        if elapsed >= 1.0 {
+            self.fps = self.fps_frames as f32 / elapsed;
+            self.fps_frames = 0;
+            self.fps_start = Instant::now();
+        }
+    }
+
+    /// lives, score, menus and the frame rate on top of the scene
+    fn draw_hud(&self, game : &Game) {
+        let text = &self.text_renderer;
+        let renderer = &self.sprite_renderer;
+        let white = glm::vec3(1.0, 1.0, 1.0);
+        let center_x = game.width() as f32 / 2.0;
+        let center_y = game.height() as f32 / 2.0;
+
+        self.sprite_renderer.begin();
+        match game.state() {
+            GameState::GameActive => {
+                text.draw(renderer, &format!("Lives: {}", game.lives()), glm::vec2(5.0, 5.0), 1.0, white);
+                let score = format!("Score: {}", game.score());
+                let width = text.measure(&score, 1.0).x;
+                text.draw(renderer, &score, glm::vec2(game.width() as f32 - width - 5.0, 5.0), 1.0, white);
+                let multiplier = game.scoring().multiplier();
+                if multiplier > 1 {
+                    text.draw_centered(renderer, &format!("Combo x{}", multiplier), center_x, 5.0, 1.0, glm::vec3(1.0, 0.8, 0.2));
+                }
+            },
+            GameState::GameMenu => {
+                if let Some(level) = game.current_level() {
+                    let header = level.header();
+                    text.draw_centered(renderer, &header.name, center_x, center_y - 40.0, 1.5, white);
+                    if !header.author.is_empty() {
+                        text.draw_centered(renderer, &format!("by {}", header.author), center_x, center_y + 5.0, 0.75, white);
+                    }
+                }
+                text.draw_centered(renderer, "Press ENTER to start", center_x, center_y + 40.0, 1.0, white);
+                text.draw_centered(renderer, "Press W or S to select level", center_x, center_y + 70.0, 0.75, white);
+                if let Some(best) = game.high_scores().best_time(&game.level_name()) {
+                    text.draw_centered(renderer, &format!("Best time: {:.1}s", best), center_x, center_y + 100.0, 0.75, white);
+                }
+                self.draw_high_scores(game, center_y + 140.0);
+            },
+            GameState::GameWin => {
+                text.draw_centered(renderer, "You WON!!!", center_x, center_y - 40.0, 1.5, glm::vec3(0.0, 1.0, 0.0));
+                text.draw_centered(renderer, &format!("Score: {}", game.score()), center_x, center_y + 5.0, 1.0, white);
+                let scoring = game.scoring();
+                let mut time = format!("Time: {:.1}s  Bonus: {}", scoring.level_time(), scoring.time_bonus());
+                if game.is_new_best_time() {
+                    time.push_str("  New best time!");
+                }
+                text.draw_centered(renderer, &time, center_x, center_y + 30.0, 0.75, white);
+                self.draw_name_entry(game, "Press ENTER to continue or ESC to quit", center_y + 60.0);
+            },
+            GameState::GameOver => {
+                text.draw_centered(renderer, "GAME OVER", center_x, center_y - 40.0, 1.5, glm::vec3(1.0, 0.3, 0.3));
+                text.draw_centered(renderer, &format!("Score: {}", game.score()), center_x, center_y + 5.0, 1.0, white);
+                self.draw_name_entry(game, "Press ENTER to retry or ESC to quit", center_y + 40.0);
+            },
+        }
+        if self.show_fps {
+            let fps = format!("{:.0} FPS", self.fps);
+            let size = text.measure(&fps, 0.75);
+            text.draw(renderer, &fps, glm::vec2(game.width() as f32 - size.x - 5.0, game.height() as f32 - size.y - 5.0), 0.75, glm::vec3(1.0, 1.0, 0.0));
+        }
+        self.sprite_renderer.flush();
+    }
+
+    /// the name being typed for a new high score, or `prompt` when there is
+    /// none
+    fn draw_name_entry(&self, game : &Game, prompt : &str, y : f32) {
+        let text = &self.text_renderer;
+        let renderer = &self.sprite_renderer;
+        let center_x = game.width() as f32 / 2.0;
+        match game.name_entry() {
+            Some(name) => {
+                text.draw_centered(renderer, "New high score! Enter your name:", center_x, y, 0.75, glm::vec3(1.0, 0.8, 0.2));
+                // blinking cursor
+                let cursor = if game.time().fract() < 0.5 { "_" } else { " " };
+                text.draw_centered(renderer, &format!("{}{}", name, cursor), center_x, y + 30.0, 1.0, glm::vec3(1.0, 1.0, 1.0));
+            },
+            None => text.draw_centered(renderer, prompt, center_x, y, 0.75, glm::vec3(1.0, 1.0, 1.0)),
+        }
+    }
+
+    /// the top of the high score table, one line per score
+    fn draw_high_scores(&self, game : &Game, y : f32) {
+        let text = &self.text_renderer;
+        let renderer = &self.sprite_renderer;
+        let center_x = game.width() as f32 / 2.0;
+        let lines : Vec<String> = game.high_scores().scores().iter().take(5).enumerate()
+            .map(|(place, entry)| format!("{:>2}. {:<12} {:>7}  {}", place + 1, entry.name, entry.score, entry.level))
+            .collect();
+        // left aligned under each other, the block as a whole is centred
+        let width = lines.iter().map(|line| text.measure(line, 0.75).x).fold(0.0, f32::max);
+        let line_height = text.font().line_height() * 0.75;
+        for (index, line) in lines.iter().enumerate() {
+            let position = glm::vec2(center_x - width / 2.0, y + index as f32 * line_height);
+            text.draw(renderer, line, position, 0.75, glm::vec3(0.8, 0.8, 0.8));
+        }
+    }
+
+    /// draw the particles as one batch with additive blending
+    fn draw_particles(&self, game : &Game) {
+        let particles = game.particles();
+        let texture = match self.texture(particles.sprite()) {
+            Some(texture) => texture,
+            None => return,
+        };
+
+        let gl = self.resource_manager.get_gl();
+        unsafe { gl.blend_func(glow::SRC_ALPHA, glow::ONE); }
+        self.sprite_renderer.begin();
+        for particle in particles.particles() {
+            self.sprite_renderer.submit(texture.texture(), &SpriteQuad {
+                position : particle.position,
+                size : glm::vec2(particle.size, particle.size),
+                rotate : 0.0,
+                color : particle.color,
+                uv : texture.uv(),
+            });
+        }
+        self.sprite_renderer.flush();
+        unsafe { gl.blend_func(glow::SRC_ALPHA, glow::ONE_MINUS_SRC_ALPHA); }
+    }
+
+    /// draw the game, `alpha` is how far the simulation has progressed
+    /// towards the next fixed update, used to interpolate moving objects
+    pub fn render(&mut self, game : &Game, alpha : f32) {
+        if let Err(err) = self.load_sprites(game) {
+            eprintln!("Error: {:#}", anyhow::Error::new(err));
+        }
+
+        // tint the background to show which state the game is in
+        let background_color = match game.state() {
+            GameState::GameActive => glm::vec3(1.0, 1.0, 1.0),
+            GameState::GameMenu => glm::vec3(0.5, 0.5, 0.5),
+            GameState::GameWin => glm::vec3(0.5, 1.0, 0.5),
+            GameState::GameOver => glm::vec3(1.0, 0.4, 0.4),
+        };
+
+        self.post_processor.begin_render();
+        self.sprite_renderer.begin();
+
+        // draw background
+        if let Some(texture) = self.texture(game.background_sprite()) {
+            self.sprite_renderer.draw_region(
+                texture,
+                glm::vec2(0.0, 0.0),
+                glm::vec2(game.width() as f32, game.height() as f32),
+                0.0,
+                background_color
+            );
+        }
+
+        self.sprite_renderer.set_layer(1);
+        if let Some(level) = game.current_level() {
+            level.bricks().iter()
+                .filter(|brick| !brick.is_destroyed())
+                .for_each(|brick| self.draw_object(brick.object(), 1.0));
+        }
+
+        self.sprite_renderer.flush();
+        self.draw_particles(game);
+        self.sprite_renderer.begin();
+
+        self.sprite_renderer.set_layer(2);
+        game.power_ups().iter()
+            .for_each(|power_up| self.draw_object(power_up.game_object(), alpha));
+        self.draw_object(game.player(), alpha);
+        game.balls().iter()
+            .for_each(|ball| self.draw_object(ball.game_object(), alpha));
+
+        self.sprite_renderer.flush();
+
+        self.post_processor.end_render();
+
+        // the scene and the HUD go to the letterboxed part of the window
+        let (x, y, width, height) = self.viewport.gl_rect();
+        unsafe { self.resource_manager.get_gl().viewport(x, y, width, height); }
+
+        self.post_processor.shake = game.is_shaking();
+        self.post_processor.chaos = game.is_chaos();
+        self.post_processor.confuse = game.is_confused();
+        self.post_processor.render(game.time());
+
+        // text goes on top of the post processing, so the effects never
+        // make it unreadable
+        self.draw_hud(game);
+        self.count_frame();
+    }
+}
+
+/// the texture unit and the projection of a `width` by `height` playfield
+fn set_sprite_uniforms(sprite_shader : &Shader, width : u32, height : u32) {
+    let projection = glm::ortho(
+        0.0,
+        width as f32,
+        height as f32,
+        0.0,
+        -1.0,
+        1.0);
+
+    // select shader before setting uniforms
+    sprite_shader.use_program();
+    sprite_shader.set_uniform_i32("image", 0);
+    sprite_shader.set_uniform_mat4("projection", &projection);
+}
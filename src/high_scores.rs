@@ -0,0 +1,204 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+
+use crate::error::ResourceError;
+
+// number of scores kept in the table
+const MAX_ENTRIES : usize = 10;
+// longest name that can be entered
+pub const MAX_NAME_LENGTH : usize = 12;
+
+/// One line of the high score table
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HighScore {
+    pub name : String,
+    pub score : u32,
+    /// name of the level it was scored on
+    pub level : String,
+}
+
+/// The best scores, highest first, and the fastest time for every level
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HighScores {
+    scores : Vec<HighScore>,
+    /// seconds, by level name
+    best_times : BTreeMap<String, f32>,
+}
+
+impl HighScores {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// the high score file in the data directory of the user
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::data_dir().map(|dir| dir.join("rust_breakout").join("highscores.toml"))
+    }
+
+    /// read the table from `path`, a missing file gives an empty table. A
+    /// file that can not be parsed is moved aside to `<path>.corrupt`, so it
+    /// is not overwritten, and an empty table is used instead.
+    pub fn load(path : &Path) -> Result<Self, ResourceError> {
+        let content = match std::fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Self::new()),
+            Err(source) => return Err(ResourceError::Io { path : path.display().to_string(), source }),
+        };
+
+        match toml::from_str::<Self>(&content) {
+            Ok(mut high_scores) => {
+                high_scores.sort();
+                Ok(high_scores)
+            },
+            Err(err) => {
+                let mut backup = path.as_os_str().to_owned();
+                backup.push(".corrupt");
+                let backup = PathBuf::from(backup);
+                let _ = std::fs::rename(path, &backup);
+                eprintln!("Error: {:#}", anyhow::Error::new(ResourceError::CorruptScores {
+                    path : path.display().to_string(),
+                    backup : backup.display().to_string(),
+                    message : err.to_string(),
+                }));
+                Ok(Self::new())
+            },
+        }
+    }
+
+    /// write the table to `path`, through a temporary file so a crash never
+    /// leaves half a file behind
+    pub fn save(&self, path : &Path) -> Result<(), ResourceError> {
+        let content = toml::to_string(self).map_err(|err| ResourceError::Write {
+            path : path.display().to_string(),
+            source : std::io::Error::new(std::io::ErrorKind::InvalidData, err),
+        })?;
+
+        let write_error = |source| ResourceError::Write { path : path.display().to_string(), source };
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(write_error)?;
+        }
+        let temp_path = path.with_extension("tmp");
+        std::fs::write(&temp_path, content).map_err(write_error)?;
+        std::fs::rename(&temp_path, path).map_err(write_error)
+    }
+
+    pub fn scores(&self) -> &[HighScore] {
+        &self.scores
+    }
+
+    /// true when the score would make it into the table
+    pub fn qualifies(&self, score : u32) -> bool {
+        score > 0 && (self.scores.len() < MAX_ENTRIES || self.scores.iter().any(|entry| score > entry.score))
+    }
+
+    /// add a score, returns its place in the table counting from 0 or `None`
+    /// when it is too low
+    pub fn add(&mut self, name : &str, score : u32, level : &str) -> Option<usize> {
+        if !self.qualifies(score) {
+            return None;
+        }
+        let name : String = name.trim().chars().take(MAX_NAME_LENGTH).collect();
+        let place = self.scores.iter().position(|entry| score > entry.score).unwrap_or(self.scores.len());
+        self.scores.insert(place, HighScore {
+            name,
+            score,
+            level : level.into(),
+        });
+        self.scores.truncate(MAX_ENTRIES);
+        Some(place)
+    }
+
+    pub fn best_time(&self, level : &str) -> Option<f32> {
+        self.best_times.get(level).copied()
+    }
+
+    /// remember the time if it is the fastest for the level, returns true
+    /// when it is
+    pub fn record_time(&mut self, level : &str, seconds : f32) -> bool {
+        match self.best_time(level) {
+            Some(best) if best <= seconds => false,
+            _ => {
+                self.best_times.insert(level.into(), seconds);
+                true
+            },
+        }
+    }
+
+    /// highest score first, a hand edited file may be in any order
+    fn sort(&mut self) {
+        self.scores.sort_by_key(|entry| std::cmp::Reverse(entry.score));
+        self.scores.truncate(MAX_ENTRIES);
+        self.best_times.retain(|_, seconds| seconds.is_finite() && *seconds > 0.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    #[test]
+    fn scores_are_kept_highest_first() {
+        let mut high_scores = HighScores::new();
+        assert!(!high_scores.qualifies(0));
+        assert_eq!(high_scores.add("b", 20, "one"), Some(0));
+        assert_eq!(high_scores.add("a", 30, "one"), Some(0));
+        assert_eq!(high_scores.add("c", 10, "two"), Some(2));
+        assert_eq!(high_scores.add("d", 20, "two"), Some(2));
+        let names : Vec<&str> = high_scores.scores().iter().map(|entry| entry.name.as_str()).collect();
+        assert_eq!(names, ["a", "b", "d", "c"]);
+    }
+
+    #[test]
+    fn full_table_drops_the_lowest_score() {
+        let mut high_scores = HighScores::new();
+        for score in 1..=MAX_ENTRIES as u32 {
+            assert!(high_scores.add("name", score * 10, "one").is_some());
+        }
+        assert!(!high_scores.qualifies(10));
+        assert_eq!(high_scores.add("low", 10, "one"), None);
+        assert!(high_scores.qualifies(11));
+        assert_eq!(high_scores.add("new", 55, "one"), Some(5));
+        assert_eq!(high_scores.scores().len(), MAX_ENTRIES);
+        assert_eq!(high_scores.scores().last().unwrap().score, 20);
+    }
+
+    #[test]
+    fn names_are_trimmed_and_shortened() {
+        let mut high_scores = HighScores::new();
+        high_scores.add("  a very long player name ", 10, "one");
+        assert_eq!(high_scores.scores()[0].name, "a very long ");
+    }
+
+    #[test]
+    fn saved_table_loads_again() {
+        let dir = TempDir::new("saved_table");
+        let path = dir.path().join("scores").join("highscores.toml");
+        assert!(HighScores::load(&path).unwrap().scores().is_empty());
+
+        let mut high_scores = HighScores::new();
+        high_scores.add("a", 30, "one");
+        high_scores.add("b", 20, "two");
+        high_scores.record_time("one", 42.5);
+        high_scores.save(&path).unwrap();
+
+        let loaded = HighScores::load(&path).unwrap();
+        assert_eq!(loaded.scores(), high_scores.scores());
+        assert_eq!(loaded.best_time("one"), Some(42.5));
+    }
+
+    #[test]
+    fn corrupt_file_is_moved_aside() {
+        let dir = TempDir::new("corrupt_file");
+        let path = dir.path().join("highscores.toml");
+        std::fs::write(&path, "scores = [ not toml").unwrap();
+
+        let high_scores = HighScores::load(&path).unwrap();
+        assert!(high_scores.scores().is_empty());
+        assert!(!path.exists());
+        let backup = dir.path().join("highscores.toml.corrupt");
+        assert_eq!(std::fs::read_to_string(backup).unwrap(), "scores = [ not toml");
+    }
+}
@@ -0,0 +1,206 @@
+use std::collections::HashSet;
+use glutin::event::{ElementState, MouseButton, VirtualKeyCode};
+
+/// Keyboard and mouse state built from input events, keys can be queried as
+/// held down, or as pressed / released since the last call to `end_frame`
+#[derive(Debug, Default)]
+pub struct InputState {
+    held : HashSet<VirtualKeyCode>,
+    pressed : HashSet<VirtualKeyCode>,
+    released : HashSet<VirtualKeyCode>,
+    buttons_held : HashSet<MouseButton>,
+    buttons_pressed : HashSet<MouseButton>,
+    /// cursor in logical playfield coordinates, `None` outside the window
+    pointer : Option<glm::Vec2>,
+    pointer_moved : bool,
+}
+
+impl InputState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// feed a key event, repeated presses of a held key are ignored
+    pub fn process_key(&mut self, key : VirtualKeyCode, state : ElementState) {
+        match state {
+            ElementState::Pressed => {
+                if self.held.insert(key) {
+                    self.pressed.insert(key);
+                }
+            },
+            ElementState::Released => {
+                if self.held.remove(&key) {
+                    self.released.insert(key);
+                }
+            },
+        }
+    }
+
+    /// feed a mouse button event
+    pub fn process_mouse_button(&mut self, button : MouseButton, state : ElementState) {
+        match state {
+            ElementState::Pressed => {
+                if self.buttons_held.insert(button) {
+                    self.buttons_pressed.insert(button);
+                }
+            },
+            ElementState::Released => {
+                self.buttons_held.remove(&button);
+            },
+        }
+    }
+
+    /// feed the cursor position in logical coordinates, `None` when it
+    /// left the window
+    pub fn process_pointer(&mut self, position : Option<glm::Vec2>) {
+        self.pointer_moved |= position.is_some() && position != self.pointer;
+        self.pointer = position;
+    }
+
+    /// key is currently down
+    pub fn is_held(&self, key : VirtualKeyCode) -> bool {
+        self.held.contains(&key)
+    }
+
+    /// key went down since the last frame
+    pub fn is_pressed(&self, key : VirtualKeyCode) -> bool {
+        self.pressed.contains(&key)
+    }
+
+    /// key went up since the last frame
+    pub fn is_released(&self, key : VirtualKeyCode) -> bool {
+        self.released.contains(&key)
+    }
+
+    /// any of the keys is currently down
+    pub fn any_held(&self, keys : &[VirtualKeyCode]) -> bool {
+        keys.iter().any(|key| self.is_held(*key))
+    }
+
+    /// any of the keys went down since the last frame
+    pub fn any_pressed(&self, keys : &[VirtualKeyCode]) -> bool {
+        keys.iter().any(|key| self.is_pressed(*key))
+    }
+
+    /// mouse button went down since the last frame
+    pub fn is_button_pressed(&self, button : MouseButton) -> bool {
+        self.buttons_pressed.contains(&button)
+    }
+
+    /// the cursor position, `None` when it is outside the window
+    pub fn pointer(&self) -> Option<glm::Vec2> {
+        self.pointer
+    }
+
+    /// the cursor moved since the last frame
+    pub fn is_pointer_moved(&self) -> bool {
+        self.pointer_moved
+    }
+
+    /// forget the pressed / released keys, call once all input for a frame
+    /// has been handled
+    pub fn end_frame(&mut self) {
+        self.pressed.clear();
+        self.released.clear();
+        self.buttons_pressed.clear();
+        self.pointer_moved = false;
+    }
+
+    /// release all keys, e.g. when the window loses focus
+    pub fn clear(&mut self) {
+        self.held.clear();
+        self.buttons_held.clear();
+        self.end_frame();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn key_edges_last_one_frame() {
+        let mut input = InputState::new();
+        input.process_key(VirtualKeyCode::Left, ElementState::Pressed);
+        assert!(input.is_pressed(VirtualKeyCode::Left));
+        assert!(input.is_held(VirtualKeyCode::Left));
+        assert!(!input.is_released(VirtualKeyCode::Left));
+
+        input.end_frame();
+        assert!(!input.is_pressed(VirtualKeyCode::Left));
+        assert!(input.is_held(VirtualKeyCode::Left));
+
+        input.process_key(VirtualKeyCode::Left, ElementState::Released);
+        assert!(input.is_released(VirtualKeyCode::Left));
+        assert!(!input.is_held(VirtualKeyCode::Left));
+
+        input.end_frame();
+        assert!(!input.is_released(VirtualKeyCode::Left));
+    }
+
+    #[test]
+    fn key_repeat_is_not_a_new_press() {
+        let mut input = InputState::new();
+        input.process_key(VirtualKeyCode::Space, ElementState::Pressed);
+        input.end_frame();
+        input.process_key(VirtualKeyCode::Space, ElementState::Pressed);
+        assert!(!input.is_pressed(VirtualKeyCode::Space));
+        assert!(input.is_held(VirtualKeyCode::Space));
+
+        // releasing a key that was never down is no edge either
+        input.process_key(VirtualKeyCode::Return, ElementState::Released);
+        assert!(!input.is_released(VirtualKeyCode::Return));
+    }
+
+    #[test]
+    fn tap_within_one_frame_is_seen() {
+        let mut input = InputState::new();
+        input.process_key(VirtualKeyCode::Return, ElementState::Pressed);
+        input.process_key(VirtualKeyCode::Return, ElementState::Released);
+        assert!(input.is_pressed(VirtualKeyCode::Return));
+        assert!(input.is_released(VirtualKeyCode::Return));
+        assert!(!input.is_held(VirtualKeyCode::Return));
+        assert!(input.any_pressed(&[VirtualKeyCode::Up, VirtualKeyCode::Return]));
+        assert!(!input.any_held(&[VirtualKeyCode::Up, VirtualKeyCode::Return]));
+    }
+
+    #[test]
+    fn mouse_button_press_lasts_one_frame() {
+        let mut input = InputState::new();
+        input.process_mouse_button(MouseButton::Left, ElementState::Pressed);
+        assert!(input.is_button_pressed(MouseButton::Left));
+        input.end_frame();
+        assert!(!input.is_button_pressed(MouseButton::Left));
+        input.process_mouse_button(MouseButton::Left, ElementState::Pressed);
+        assert!(!input.is_button_pressed(MouseButton::Left));
+    }
+
+    #[test]
+    fn pointer_moves_only_when_it_changes() {
+        let mut input = InputState::new();
+        input.process_pointer(Some(glm::vec2(10.0, 20.0)));
+        assert!(input.is_pointer_moved());
+        input.end_frame();
+
+        input.process_pointer(Some(glm::vec2(10.0, 20.0)));
+        assert!(!input.is_pointer_moved());
+        input.process_pointer(None);
+        assert!(!input.is_pointer_moved());
+        assert_eq!(input.pointer(), None);
+    }
+
+    #[test]
+    fn clear_releases_everything() {
+        let mut input = InputState::new();
+        input.process_key(VirtualKeyCode::Right, ElementState::Pressed);
+        input.process_mouse_button(MouseButton::Left, ElementState::Pressed);
+        input.clear();
+        assert!(!input.is_held(VirtualKeyCode::Right));
+        assert!(!input.is_pressed(VirtualKeyCode::Right));
+        assert!(!input.is_button_pressed(MouseButton::Left));
+
+        // a press after the focus came back counts again
+        input.process_key(VirtualKeyCode::Right, ElementState::Pressed);
+        assert!(input.is_pressed(VirtualKeyCode::Right));
+    }
+}
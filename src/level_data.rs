@@ -0,0 +1,236 @@
+use std::collections::HashMap;
+use std::path::Path;
+use serde::Deserialize;
+
+use crate::assets::Assets;
+use crate::error::ResourceError;
+
+// textures used by the legacy grid format
+const LEGACY_BLOCK_TEXTURE : &str = "resources/textures/block.png";
+const LEGACY_BLOCK_SOLID_TEXTURE : &str = "resources/textures/block_solid.png";
+
+/// General settings of a level, all values are optional so the game
+/// defaults are used for anything a level does not set
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LevelHeader {
+    pub name : String,
+    pub author : String,
+    /// expected time to clear the level, in seconds
+    pub par_time : Option<f32>,
+    /// image file drawn behind the bricks
+    pub background : Option<String>,
+    /// speed of the ball when it leaves the paddle, in pixels per second
+    pub ball_speed : Option<f32>,
+    pub paddle_width : Option<f32>,
+    /// chance that a destroyed brick drops a power-up, from 0 to 1
+    pub power_up_chance : Option<f32>,
+}
+
+/// What a brick id in the grid stands for
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BrickKind {
+    pub color : [f32; 3],
+    /// image file of the brick, the default block when not set
+    pub texture : Option<String>,
+    /// image files shown after the first, second, ... hit, the last one is
+    /// kept for any further hits
+    pub damage_textures : Vec<String>,
+    /// number of hits needed to destroy the brick
+    pub hit_points : u32,
+    pub score : u32,
+    /// solid bricks deflect the ball but can not be destroyed
+    pub solid : bool,
+}
+
+impl Default for BrickKind {
+    fn default() -> Self {
+        Self {
+            color : [1.0, 1.0, 1.0],
+            texture : None,
+            damage_textures : Vec::new(),
+            hit_points : 1,
+            score : 10,
+            solid : false,
+        }
+    }
+}
+
+impl BrickKind {
+    pub fn texture(&self) -> &str {
+        match &self.texture {
+            Some(texture) => texture,
+            None if self.solid => LEGACY_BLOCK_SOLID_TEXTURE,
+            None => LEGACY_BLOCK_TEXTURE,
+        }
+    }
+}
+
+/// Contents of a level file, brick id 0 is always an empty cell
+#[derive(Debug, Clone)]
+pub struct LevelData {
+    pub header : LevelHeader,
+    pub palette : HashMap<u32, BrickKind>,
+    pub grid : Vec<Vec<u32>>,
+}
+
+/// Layout of a `.toml` level file
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LevelFile {
+    #[serde(default)]
+    header : LevelHeader,
+    palette : HashMap<String, BrickKind>,
+    bricks : toml::Spanned<String>,
+}
+
+impl LevelData {
+    /// load a `.toml` level, any other extension is read as a legacy grid
+    /// of brick ids 0 to 5
+    pub fn load(assets : &Assets, level_path : &str) -> Result<Self, ResourceError> {
+        let content = assets.read_to_string(level_path)?;
+        let is_toml = Path::new(level_path).extension().is_some_and(|ext| ext == "toml");
+        let mut level = if is_toml {
+            Self::parse_toml(level_path, &content)?
+        } else {
+            Self::parse_legacy(level_path, &content)?
+        };
+
+        if level.header.name.is_empty() {
+            level.header.name = Path::new(level_path).file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or_default();
+        }
+        if level.grid.is_empty() {
+            return Err(ResourceError::EmptyLevel { path : level_path.into() });
+        }
+        Ok(level)
+    }
+
+    fn parse_toml(level_path : &str, content : &str) -> Result<Self, ResourceError> {
+        let file : LevelFile = toml::from_str(content)
+            .map_err(|source| ResourceError::LevelFormat { path : level_path.into(), source })?;
+
+        let mut palette = HashMap::new();
+        for (id, kind) in file.palette {
+            let id = match id.parse::<u32>() {
+                Ok(id) if id != 0 => id,
+                _ => return Err(ResourceError::LevelData {
+                    path : level_path.into(),
+                    message : format!("palette id '{}' is not a number above 0", id),
+                }),
+            };
+            if kind.hit_points == 0 && !kind.solid {
+                return Err(ResourceError::LevelData {
+                    path : level_path.into(),
+                    message : format!("brick {} needs at least one hit point", id),
+                });
+            }
+            palette.insert(id, kind);
+        }
+
+        let first_line = string_start_line(content, file.bricks.span().start);
+        let grid = parse_grid(level_path, file.bricks.get_ref(), first_line, |id| id == 0 || palette.contains_key(&id))?;
+        Ok(Self {
+            header : file.header,
+            palette,
+            grid,
+        })
+    }
+
+    fn parse_legacy(level_path : &str, content : &str) -> Result<Self, ResourceError> {
+        let palette = legacy_palette();
+        let grid = parse_grid(level_path, content, 1, |_| true)?;
+        Ok(Self {
+            header : LevelHeader::default(),
+            palette,
+            grid,
+        })
+    }
+}
+
+/// the colours and solidity the original grid files were made for
+fn legacy_palette() -> HashMap<u32, BrickKind> {
+    let brick = |color : [f32; 3], solid : bool| BrickKind {
+        color,
+        solid,
+        score : if solid { 0 } else { 10 },
+        ..BrickKind::default()
+    };
+
+    let mut palette = HashMap::new();
+    palette.insert(1, brick([0.8, 0.8, 0.7], true));
+    palette.insert(2, brick([0.2, 0.6, 1.0], false));
+    palette.insert(3, brick([0.0, 0.7, 0.0], false));
+    palette.insert(4, brick([0.8, 0.8, 0.4], false));
+    palette.insert(5, brick([1.0, 0.5, 0.0], false));
+    palette
+}
+
+/// line of the file the string starting at byte `start` begins on, a
+/// newline right after the opening quotes of a multi-line string is not
+/// part of it
+fn string_start_line(content : &str, start : usize) -> usize {
+    let line = content[..start].lines().count().max(1);
+    let rest = &content[start..];
+    let multi_line = rest.starts_with("\"\"\"") || rest.starts_with("'''");
+    if multi_line && (rest[3..].starts_with('\n') || rest[3..].starts_with("\r\n")) {
+        line + 1
+    } else {
+        line
+    }
+}
+
+/// rows of whitespace separated brick ids, empty lines are skipped, errors
+/// name the line of the file counting from `first_line`
+fn parse_grid(level_path : &str, content : &str, first_line : usize, is_known : impl Fn(u32) -> bool) -> Result<Vec<Vec<u32>>, ResourceError> {
+    let mut tile_data = Vec::new();
+    for (line_number, line) in content.lines().enumerate() {
+
+        let row = line.split_ascii_whitespace().map(|s|
+            match s.parse::<u32>() {
+                Ok(id) if is_known(id) => Ok(id),
+                _ => Err(ResourceError::LevelParse {
+                    path : level_path.into(),
+                    line : first_line + line_number,
+                    token : s.into(),
+                }),
+            }).collect::<Result<Vec<u32>, _>>()?;
+
+        if !row.is_empty() {
+            tile_data.push(row);
+        }
+    }
+    Ok(tile_data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_error_line(content : &str) -> usize {
+        match LevelData::parse_toml("test.toml", content) {
+            Err(ResourceError::LevelParse { line, .. }) => line,
+            other => panic!("expected a parse error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn errors_in_bricks_name_the_line_of_the_file() {
+        let content = "# comment\n\nbricks = \"\"\"\n1 1\n\n1 x\n\"\"\"\n\n[palette.1]\n";
+        assert_eq!(parse_error_line(content), 6);
+        let content = "bricks = \"\"\"1 1\n1 x\"\"\"\n[palette.1]\n";
+        assert_eq!(parse_error_line(content), 2);
+        let content = "bricks = '1 2'\n[palette.1]\n";
+        assert_eq!(parse_error_line(content), 1);
+    }
+
+    #[test]
+    fn errors_in_legacy_levels_name_the_line() {
+        match LevelData::parse_legacy("test.lvl", "1 1\n\n1 x\n") {
+            Err(ResourceError::LevelParse { line : 3, .. }) => (),
+            other => panic!("expected a parse error on line 3, got {:?}", other),
+        }
+    }
+}
@@ -1,5 +1,6 @@
 use glow::*;
-use glutin::event::{Event, VirtualKeyCode, WindowEvent, ElementState, MouseButton};
+use glutin::event::{Event, VirtualKeyCode, WindowEvent, ElementState};
+use glutin::window::Fullscreen;
 use glutin::event_loop::ControlFlow;
 use std::rc::Rc;
 
@@ -12,17 +13,94 @@ pub mod game;
 pub mod game_object;
 pub mod game_level;
 pub mod ball_object;
+pub mod input;
+pub mod timestep;
+pub mod sprite;
+pub mod game_renderer;
+pub mod error;
+pub mod assets;
+pub mod texture_atlas;
+pub mod level_data;
+pub mod power_up;
+pub mod post_processor;
+pub mod particle_generator;
+pub mod text_renderer;
+pub mod audio;
+pub mod scoring;
+pub mod high_scores;
+pub mod viewport;
+pub mod replay;
+pub mod screenshot;
+pub mod file_watcher;
+#[cfg(test)]
+mod render_tests;
+#[cfg(test)]
+mod test_util;
 
+use anyhow::Context;
+use assets::Assets;
+use audio::Audio;
 use game::Game;
+use file_watcher::FileWatcher;
+use game_renderer::GameRenderer;
+use high_scores::HighScores;
+use replay::{InputEvent, Recorder, Replay, ReplayPlayer};
+use screenshot::FrameCapture;
+use timestep::FixedTimestep;
 
 use resource_manager::*;
 
 extern crate nalgebra_glm as glm;
 
+// logical size of the playfield, it is scaled to fit the window
 const SCR_WIDTH: u32 = 800;
 const SCR_HEIGHT: u32 = 600;
 
-pub fn main() {
+// simulation updates per second
+const SIMULATION_RATE: f32 = 120.0;
+// most simulation updates done for a single rendered frame
+const MAX_STEPS_PER_FRAME: u32 = 10;
+// frames per second of the game time while frames are captured
+const CAPTURE_RATE: f32 = 60.0;
+// folder F12 saves screenshots to
+const SCREENSHOT_DIR: &str = "screenshots";
+
+pub fn main() -> anyhow::Result<()> {
+    // status messages are shown, RUST_LOG=debug adds more detail
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
+
+    let args : Vec<String> = std::env::args().collect();
+    let path_arg = |name : &str| -> anyhow::Result<Option<std::path::PathBuf>> {
+        match args.iter().position(|arg| arg == name) {
+            Some(index) => {
+                let path = args.get(index + 1).with_context(|| format!("{} needs a file name", name))?;
+                Ok(Some(path.into()))
+            },
+            None => Ok(None),
+        }
+    };
+
+    // breakout --assets <dir or zip> reads assets from there before the
+    // ones built in, --dev edits them in the current directory
+    let dev_mode = args.iter().any(|arg| arg == "--dev");
+    let assets = match path_arg("--assets")? {
+        Some(path) => Assets::with_override(&path).context("Failed to open the assets")?,
+        None if dev_mode => Assets::with_override(std::path::Path::new(".")).context("Failed to open the assets")?,
+        None => Assets::embedded(),
+    };
+    let assets = Rc::new(assets);
+
+    // breakout --verify-replay <file> plays a replay without a window and
+    // fails when it does not end like it was recorded
+    if let Some(path) = path_arg("--verify-replay")? {
+        let replay = Replay::load(&path).context("Failed to load the replay")?;
+        let mut game = Game::with_assets(SCR_WIDTH, SCR_HEIGHT, assets);
+        game.init().context("Failed to initialize the game")?;
+        let checksum = replay.verify(&mut game).context("Replay verification failed")?;
+        println!("Replay {} ok, {} ticks, checksum {:016x}", path.display(), replay.ticks(), checksum);
+        return Ok(());
+    }
+
     unsafe 
     {
         let event_loop = glutin::event_loop::EventLoop::new();
@@ -32,14 +110,20 @@ pub fn main() {
         let window = glutin::ContextBuilder::new()
             .with_vsync(true)
             .build_windowed(window_builder, &event_loop)
-            .unwrap()
+            .context("Failed to create the window")?
             .make_current()
-            .unwrap();
+            .map_err(|(_, err)| err)
+            .context("Failed to activate the GL context")?;
         let gl=Rc::new(glow::Context::from_loader_function(|s| window.get_proc_address(s) as *const _));
 
 
-        let resource_manager = Rc::new( ResourceManager::new(gl.clone()));
-        let mut game = Game::new(resource_manager.clone(), SCR_WIDTH, SCR_HEIGHT);
+        let resource_manager = Rc::new( ResourceManager::with_assets(gl.clone(), assets.clone()));
+        let mut game = Game::with_assets(SCR_WIDTH, SCR_HEIGHT, assets.clone());
+        let mut game_renderer = GameRenderer::new(resource_manager.clone(), SCR_WIDTH, SCR_HEIGHT)
+            .context("Failed to create the renderer")?;
+        // on a high DPI screen the window has more pixels than the playfield
+        let physical_size = window.window().inner_size();
+        game_renderer.resize(physical_size.width, physical_size.height);
 
         // OpenGL configuration
         // --------------------
@@ -48,88 +132,220 @@ pub fn main() {
 
         // initialize game
         // ---------------
-        game.init();
+        game.init().context("Failed to initialize the game")?;
+        // without a data directory the high scores are not kept
+        if let Some(path) = HighScores::default_path() {
+            if let Err(err) = game.load_high_scores(path) {
+                eprintln!("Error: {:#}", anyhow::Error::new(err));
+            }
+        }
+        game_renderer.load_sprites(&game).context("Failed to load the game sprites")?;
+
+        // the game can be played without sound, so audio errors are not fatal
+        let mut audio = Audio::new(Audio::default_backend());
+        if let Err(err) = audio.load(&assets).and_then(|_| audio.start_music(&assets)) {
+            eprintln!("Error: {:#}", anyhow::Error::new(err));
+        }
+
+        // breakout --dump-atlas <file.png> writes the sprite atlas and its layout
+        if let Some(path) = path_arg("--dump-atlas")? {
+            game_renderer.dump_atlas(&path).context("Failed to dump the sprite atlas")?;
+            println!("Sprite atlas written to {}", path.display());
+        }
 
 
-        let mut _is_dragging = false;
-        let mut _last_x = SCR_WIDTH as f32 / 2.0;
-        let mut _last_y = SCR_HEIGHT as f32 / 2.0;
+        // the simulation runs at a fixed rate, rendering as fast as vsync allows
+        let mut timestep = FixedTimestep::new(SIMULATION_RATE).with_max_steps(MAX_STEPS_PER_FRAME);
+        let mut last_frame_time = std::time::Instant::now();
 
-        const DESIRED_FRAME_TIME :f32 = 0.02;
-        let mut last_draw_time = std::time::Instant::now();
+        // breakout --record <file> writes all input to a replay on exit,
+        // breakout --replay <file> plays one back instead of taking input
+        let record_path = path_arg("--record")?;
+        let mut recorder = record_path.as_ref().map(|_| Recorder::new(&game, timestep.step()));
+        let mut player = match path_arg("--replay")? {
+            Some(path) => {
+                let replay = Replay::load(&path).context("Failed to load the replay")?;
+                Some(ReplayPlayer::new(replay, &mut game))
+            },
+            None => None,
+        };
+        // breakout --capture-frames <dir> [--capture-every <n>] writes every
+        // n-th frame to numbered PNGs
+        let mut frame_capture = match path_arg("--capture-frames")? {
+            Some(dir) => {
+                let every = match args.iter().position(|arg| arg == "--capture-every") {
+                    Some(index) => args.get(index + 1)
+                        .and_then(|every| every.parse().ok())
+                        .context("--capture-every needs a number of frames")?,
+                    None => 1,
+                };
+                Some(FrameCapture::new(&dir, every).context("Failed to start the frame capture")?)
+            },
+            None => None,
+        };
+        let mut take_screenshot = false;
+        // breakout --dev reloads shaders, textures and levels when their
+        // files change
+        let mut file_watcher = dev_mode.then(|| FileWatcher::new(assets.clone()));
+
+        // input reaches the game once per frame, so it can be recorded
+        let mut pending_input : Vec<InputEvent> = Vec::new();
         
         event_loop.run(move |event, _, control_flow| {
             
-            let now =  std::time::Instant::now();
-            let elapsed_time = now.duration_since(last_draw_time).as_secs_f32();
-
-            if  elapsed_time > DESIRED_FRAME_TIME {
-                window.window().request_redraw();
-                last_draw_time = now;
-            }
-
             match event {
                 Event::RedrawRequested(_) => {
 
                     // update game state
                     // -----------------
-                    game.update(0.016f32);
+                    let now =  std::time::Instant::now();
+                    let mut frame_time = now.duration_since(last_frame_time).as_secs_f32();
+                    last_frame_time = now;
+                    // writing the images is slow, the captured game runs at
+                    // a steady rate instead so the video plays smoothly
+                    if frame_capture.is_some() {
+                        frame_time = 1.0 / CAPTURE_RATE;
+                    }
+
+                    if let Some(file_watcher) = file_watcher.as_mut().filter(|file_watcher| file_watcher.is_due()) {
+                        // levels may add sprites, those are watched once loaded
+                        file_watcher.watch_all(resource_manager.watched_files());
+                        file_watcher.watch_all(game.level_files());
+                        let changed = file_watcher.poll();
+                        if !changed.is_empty() {
+                            game.reload_levels(&changed);
+                            game_renderer.reload_changed(&changed);
+                        }
+                    }
+
+                    match player.as_mut() {
+                        Some(player) if !player.is_finished(&game) => {
+                            for _ in 0..timestep.advance(frame_time) {
+                                player.tick(&mut game);
+                            }
+                            if player.is_finished(&game) {
+                                match player.finish(&mut game) {
+                                    Ok(checksum) => println!("Replay finished, checksum {:016x}", checksum),
+                                    Err(err) => eprintln!("Error: {:#}", anyhow::Error::new(err)),
+                                }
+                            }
+                        },
+                        // a finished replay stays on its last frame
+                        Some(_) => {},
+                        None => {
+                            let input = std::mem::take(&mut pending_input);
+                            if let Some(recorder) = recorder.as_mut() {
+                                recorder.record_frame(game.ticks(), &input);
+                            }
+                            input.iter().for_each(|event| event.apply(&mut game));
+                            game.process_input();
+                            for _ in 0..timestep.advance(frame_time) {
+                                game.update(timestep.step());
+                            }
+                        },
+                    }
+                    for event in game.take_events() {
+                        audio.handle_event(&event);
+                    }
 
                     // DRAW HERE
                     gl.clear_color(0.1, 0.1, 0.1, 1.0);
                     gl.clear(glow::COLOR_BUFFER_BIT | glow::DEPTH_BUFFER_BIT);
 
-                    game.render(0.0);
+                    game_renderer.render(&game, timestep.alpha());
+                    if take_screenshot {
+                        take_screenshot = false;
+                        match screenshot::save_screenshot(&game_renderer.capture(), std::path::Path::new(SCREENSHOT_DIR)) {
+                            Ok(path) => println!("Screenshot written to {}", path.display()),
+                            Err(err) => eprintln!("Error: {:#}", anyhow::Error::new(err)),
+                        }
+                    }
+                    if let Some(frame_capture) = frame_capture.as_mut() {
+                        frame_capture.frame(|| game_renderer.capture());
+                    }
                     window.swap_buffers().unwrap();
                 },
 
-                Event::WindowEvent { ref event, .. } => match event {                    
-                    WindowEvent::Resized(physical_size) => window.resize(*physical_size),
-                    WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
-                    WindowEvent::KeyboardInput { device_id:_, input, is_synthetic:_ } => {
-                        match input.virtual_keycode {
-                            Some(key) => {
-                                match key {
-                                    VirtualKeyCode::Escape => *control_flow = glutin::event_loop::ControlFlow::Exit,
-                                    key => (game.process_input(0.016f32, key )),
-                                }
-                            },
-                            _ => (),
-                        }
-                    },
-                    WindowEvent::CursorMoved { device_id:_, position, .. } => {
-                        //println!("Move to {:?}", position);
-                        let new_x = position.x as f32;
-                        let new_y = position.y as f32;
-
-                        // if is_dragging {
-                        //     camera.mouse_interact(new_x - last_x, new_y - last_y);                            
-                        // }
-                        _last_x = new_x;
-                        _last_y = new_y;
-                    },
-
-                    WindowEvent::MouseInput { device_id:_, state, button, .. } => {
-                        if state == &ElementState::Pressed && button == &MouseButton::Left {
-                            _is_dragging = true;
-                        } else {
-                            _is_dragging = false;
-                        }
-                    },
-
-                    WindowEvent::MouseWheel { device_id:_, delta, phase :_, .. } => {
-                        match delta {
-                            glutin::event::MouseScrollDelta::LineDelta(_x,_y) => {
-                                // camera.scroll_wheel_interact(*y);
-                            },
-                            _ => (),
-                        }
-                    },
-                    _=> {}
+                Event::WindowEvent { ref event, .. } => {
+                    let input_event = match event {
+                        WindowEvent::Resized(physical_size) => {
+                            window.resize(*physical_size);
+                            game_renderer.resize(physical_size.width, physical_size.height);
+                            None
+                        },
+                        WindowEvent::ScaleFactorChanged { new_inner_size, .. } => {
+                            window.resize(**new_inner_size);
+                            game_renderer.resize(new_inner_size.width, new_inner_size.height);
+                            None
+                        },
+                        WindowEvent::CloseRequested => {
+                            *control_flow = ControlFlow::Exit;
+                            None
+                        },
+                        WindowEvent::Focused(false) => Some(InputEvent::ReleaseKeys),
+                        WindowEvent::ReceivedCharacter(c) => Some(InputEvent::Char(*c)),
+                        WindowEvent::KeyboardInput { device_id:_, input, is_synthetic:_ } => {
+                            match input.virtual_keycode {
+                                Some(VirtualKeyCode::Escape) => {
+                                    *control_flow = glutin::event_loop::ControlFlow::Exit;
+                                    None
+                                },
+                                Some(VirtualKeyCode::F3) if input.state == ElementState::Pressed => {
+                                    game_renderer.toggle_fps();
+                                    None
+                                },
+                                Some(VirtualKeyCode::F12) if input.state == ElementState::Pressed => {
+                                    take_screenshot = true;
+                                    None
+                                },
+                                Some(VirtualKeyCode::F11) if input.state == ElementState::Pressed => {
+                                    let fullscreen = match window.window().fullscreen() {
+                                        Some(_) => None,
+                                        None => Some(Fullscreen::Borderless(None)),
+                                    };
+                                    window.window().set_fullscreen(fullscreen);
+                                    None
+                                },
+                                Some(key @ (VirtualKeyCode::Minus | VirtualKeyCode::Equals)) if input.state == ElementState::Pressed => {
+                                    let mut mixer = *audio.mixer();
+                                    mixer.master += if key == VirtualKeyCode::Minus { -0.1 } else { 0.1 };
+                                    audio.set_mixer(mixer);
+                                    None
+                                },
+                                Some(key) => Some(InputEvent::Key(key, input.state)),
+                                _ => None,
+                            }
+                        },
+                        WindowEvent::CursorMoved { device_id:_, position, .. } => {
+                            // the cursor is in physical pixels, the game works in the logical playfield
+                            let physical = glm::vec2(position.x as f32, position.y as f32);
+                            let logical = game_renderer.viewport().to_logical(physical);
+                            Some(InputEvent::Pointer(Some([logical.x, logical.y])))
+                        },
+                        WindowEvent::CursorLeft { .. } => Some(InputEvent::Pointer(None)),
+                        WindowEvent::MouseInput { device_id:_, state, button, .. } => {
+                            Some(InputEvent::MouseButton(*button, *state))
+                        },
+                        _=> None
+                    };
+
+                    // a replay gets no input other than its own
+                    if let (Some(input_event), None) = (input_event, player.as_ref()) {
+                        pending_input.push(input_event);
+                    }
                 },
 
                 Event::LoopDestroyed => {
-                    // CLEANUP  
+                    if let Some(frame_capture) = frame_capture.take() {
+                        println!("{} frames captured", frame_capture.written());
+                    }
+                    if let (Some(recorder), Some(path)) = (recorder.take(), record_path.as_ref()) {
+                        let replay = recorder.finish(&game);
+                        match replay.save(path) {
+                            Ok(()) => println!("Replay written to {}, {} ticks, checksum {:016x}", path.display(), replay.ticks(), replay.checksum()),
+                            Err(err) => eprintln!("Error: {:#}", anyhow::Error::new(err)),
+                        }
+                    }
                 },
                 
                 Event::NewEvents(_) => {}
@@ -137,7 +353,7 @@ pub fn main() {
                 Event::UserEvent(_) => {}
                 Event::Suspended => {}
                 Event::Resumed => {}
-                Event::MainEventsCleared => {}
+                Event::MainEventsCleared => window.window().request_redraw(),
                 Event::RedrawEventsCleared => {}
             }
         } );
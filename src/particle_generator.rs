@@ -0,0 +1,152 @@
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+use crate::ball_object::BallObject;
+use crate::sprite::SpriteId;
+
+/// A single particle, it is alive while `life` is above 0
+#[derive(Debug, Clone, Copy)]
+pub struct Particle {
+    pub position : glm::Vec2,
+    pub velocity : glm::Vec2,
+    pub size : f32,
+    pub color : glm::Vec4,
+    /// seconds left to live
+    pub life : f32,
+    /// seconds the particle lives in total
+    pub lifetime : f32,
+}
+
+impl Particle {
+    fn dead() -> Self {
+        Self {
+            position : glm::vec2(0.0, 0.0),
+            velocity : glm::vec2(0.0, 0.0),
+            size : 0.0,
+            color : glm::vec4(1.0, 1.0, 1.0, 0.0),
+            life : 0.0,
+            lifetime : 0.0,
+        }
+    }
+
+    pub fn is_alive(&self) -> bool {
+        self.life > 0.0
+    }
+}
+
+// Seed of the particle random numbers, particles have their own generator
+// so showing them never changes how the game plays
+const PARTICLE_SEED : u64 = 0x5a4c;
+
+/// Fixed pool of particles that are drawn with additive blending, when the
+/// pool is full a new particle replaces the one closest to its end
+pub struct ParticleGenerator {
+    particles : Vec<Particle>,
+    last_used : usize,
+    sprite : SpriteId,
+    rng : ChaCha8Rng,
+}
+
+impl ParticleGenerator {
+    pub fn new(amount : usize, sprite : SpriteId) -> Self {
+        Self {
+            particles : vec![Particle::dead(); amount],
+            last_used : 0,
+            sprite,
+            rng : ChaCha8Rng::seed_from_u64(PARTICLE_SEED),
+        }
+    }
+
+    /// the sprite all particles are drawn with
+    pub fn sprite(&self) -> SpriteId {
+        self.sprite
+    }
+
+    /// the particles that are alive
+    pub fn particles(&self) -> impl Iterator<Item = &Particle> {
+        self.particles.iter().filter(|particle| particle.is_alive())
+    }
+
+    /// start a particle centred on `position`
+    pub fn emit(&mut self, position : glm::Vec2, velocity : glm::Vec2, size : f32, color : glm::Vec3, lifetime : f32) {
+        if self.particles.is_empty() {
+            return;
+        }
+        let index = self.unused_particle();
+        self.particles[index] = Particle {
+            position : position - glm::vec2(size, size).scale(0.5),
+            velocity,
+            size,
+            color : glm::vec4(color.x, color.y, color.z, 1.0),
+            life : lifetime,
+            lifetime,
+        };
+    }
+
+    /// a few particles behind a moving ball, a ball stuck to the paddle
+    /// leaves no trail
+    pub fn emit_trail(&mut self, ball : &BallObject) {
+        if ball.is_stuck() {
+            return;
+        }
+        let radius = ball.get_radius();
+        for _ in 0..2 {
+            let offset = glm::vec2(self.rng.gen_range(-0.5..0.5), self.rng.gen_range(-0.5..0.5)).scale(radius);
+            let brightness = self.rng.gen_range(0.5..1.0);
+            self.emit(
+                ball.get_center() + offset,
+                ball.get_velocity().scale(0.1),
+                radius,
+                glm::vec3(brightness, brightness, brightness),
+                1.0);
+        }
+    }
+
+    /// debris flying out of a destroyed brick, in the colour of the brick
+    pub fn emit_burst(&mut self, position : glm::Vec2, size : glm::Vec2, color : glm::Vec3) {
+        for _ in 0..20 {
+            let start = position + glm::vec2(self.rng.gen_range(0.0..size.x), self.rng.gen_range(0.0..size.y));
+            let angle : f32 = self.rng.gen_range(0.0..std::f32::consts::TAU);
+            let speed = self.rng.gen_range(50.0..200.0);
+            let size = self.rng.gen_range(6.0..14.0);
+            let lifetime = self.rng.gen_range(0.3..0.8);
+            self.emit(
+                start,
+                glm::vec2(angle.cos(), angle.sin()).scale(speed),
+                size,
+                color,
+                lifetime);
+        }
+    }
+
+    /// move the particles and fade them out towards the end of their life
+    pub fn update(&mut self, dt : f32) {
+        for particle in self.particles.iter_mut().filter(|particle| particle.is_alive()) {
+            particle.life -= dt;
+            particle.position += particle.velocity.scale(dt);
+            particle.color.w = (particle.life / particle.lifetime).max(0.0);
+        }
+    }
+
+    /// kill all particles
+    pub fn clear(&mut self) {
+        self.particles.iter_mut().for_each(|particle| particle.life = 0.0);
+    }
+
+    /// index of a dead particle, searching on from the last one used, or the
+    /// particle with the least life left when all are alive
+    fn unused_particle(&mut self) -> usize {
+        let count = self.particles.len();
+        let found = (0..count)
+            .map(|offset| (self.last_used + offset) % count)
+            .find(|index| !self.particles[*index].is_alive());
+
+        let index = found.unwrap_or_else(|| {
+            self.particles.iter().enumerate()
+                .min_by(|(_, a), (_, b)| a.life.total_cmp(&b.life))
+                .map_or(0, |(index, _)| index)
+        });
+        self.last_used = index;
+        index
+    }
+}
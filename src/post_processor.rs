@@ -0,0 +1,187 @@
+use glow::*;
+use std::ops::Drop;
+use std::rc::Rc;
+
+use crate::error::ResourceError;
+use crate::resource_manager::ResourceManager;
+use crate::shader::Shader;
+
+// full screen quad as two triangles, <vec2 position, vec2 texCoords>
+const SCREEN_QUAD : [f32; 24] = [
+    -1.0, -1.0, 0.0, 0.0,
+     1.0,  1.0, 1.0, 1.0,
+    -1.0,  1.0, 0.0, 1.0,
+
+    -1.0, -1.0, 0.0, 0.0,
+     1.0, -1.0, 1.0, 0.0,
+     1.0,  1.0, 1.0, 1.0,
+];
+
+/// Renders the scene into an offscreen framebuffer and draws that to the
+/// screen with a full screen shader, which adds the shake, chaos and confuse
+/// effects.
+///
+/// Use `begin_render` before drawing the scene, `end_render` after it and
+/// `render` to put the result on the screen.
+pub struct PostProcessor {
+    gl : Rc<glow::Context>,
+    shader : Rc<Shader>,
+    width : u32,
+    height : u32,
+    // multisampled framebuffer the scene is drawn into, when samples > 0
+    msfbo : Option<glow::Framebuffer>,
+    rbo : Option<glow::Renderbuffer>,
+    // framebuffer with the texture the screen quad samples from
+    fbo : glow::Framebuffer,
+    texture : glow::Texture,
+    vao : glow::VertexArray,
+    vbo : glow::Buffer,
+    pub shake : bool,
+    pub chaos : bool,
+    pub confuse : bool,
+}
+
+impl PostProcessor {
+    /// create the framebuffers for a `width` by `height` scene, with
+    /// `samples` samples per pixel or no multisampling when that is 0
+    pub fn new(resource_manager : &ResourceManager, width : u32, height : u32, samples : u32) -> Result<Self, ResourceError> {
+        let shader = resource_manager.load_shader(
+            "src/shaders/post_processing.vs",
+            "src/shaders/post_processing.fs",
+            "post_processing".into()
+        )?;
+        let gl = resource_manager.get_gl();
+
+        unsafe {
+            let (msfbo, rbo) = if samples > 0 {
+                let msfbo = gl.create_framebuffer().map_err(ResourceError::Framebuffer)?;
+                let rbo = gl.create_renderbuffer().map_err(ResourceError::Framebuffer)?;
+                gl.bind_framebuffer(glow::FRAMEBUFFER, Some(msfbo));
+                gl.bind_renderbuffer(glow::RENDERBUFFER, Some(rbo));
+                gl.renderbuffer_storage_multisample(glow::RENDERBUFFER, samples as i32, glow::RGBA8, width as i32, height as i32);
+                gl.framebuffer_renderbuffer(glow::FRAMEBUFFER, glow::COLOR_ATTACHMENT0, glow::RENDERBUFFER, Some(rbo));
+                Self::check_status(&gl, "multisampled framebuffer")?;
+                (Some(msfbo), Some(rbo))
+            } else {
+                (None, None)
+            };
+
+            let fbo = gl.create_framebuffer().map_err(ResourceError::Framebuffer)?;
+            let texture = gl.create_texture().map_err(ResourceError::Framebuffer)?;
+            gl.bind_framebuffer(glow::FRAMEBUFFER, Some(fbo));
+            gl.bind_texture(glow::TEXTURE_2D, Some(texture));
+            gl.tex_image_2d(glow::TEXTURE_2D, 0, glow::RGBA as i32, width as i32, height as i32, 0, glow::RGBA, glow::UNSIGNED_BYTE, None);
+            gl.tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_MIN_FILTER, glow::LINEAR as i32);
+            gl.tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_MAG_FILTER, glow::LINEAR as i32);
+            // the chaos effect samples outside the scene, repeat it
+            gl.tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_WRAP_S, glow::REPEAT as i32);
+            gl.tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_WRAP_T, glow::REPEAT as i32);
+            gl.framebuffer_texture_2d(glow::FRAMEBUFFER, glow::COLOR_ATTACHMENT0, glow::TEXTURE_2D, Some(texture), 0);
+            Self::check_status(&gl, "framebuffer")?;
+            gl.bind_framebuffer(glow::FRAMEBUFFER, None);
+
+            let vao = gl.create_vertex_array().map_err(ResourceError::Framebuffer)?;
+            let vbo = gl.create_buffer().map_err(ResourceError::Framebuffer)?;
+            gl.bind_vertex_array(Some(vao));
+            gl.bind_buffer(glow::ARRAY_BUFFER, Some(vbo));
+            gl.buffer_data_u8_slice(glow::ARRAY_BUFFER, bytemuck::cast_slice(&SCREEN_QUAD), glow::STATIC_DRAW);
+            gl.vertex_attrib_pointer_f32(0, 4, glow::FLOAT, false, (std::mem::size_of::<f32>() * 4) as i32, 0);
+            gl.enable_vertex_attrib_array(0);
+            gl.bind_vertex_array(None);
+
+            let post_processor = Self {
+                gl,
+                shader,
+                width,
+                height,
+                msfbo,
+                rbo,
+                fbo,
+                texture,
+                vao,
+                vbo,
+                shake : false,
+                chaos : false,
+                confuse : false,
+            };
+            post_processor.set_uniforms();
+            Ok(post_processor)
+        }
+    }
+
+    /// set the uniforms that do not change between frames, needed again
+    /// when the shader was reloaded
+    pub fn set_uniforms(&self) {
+        self.shader.use_program();
+        self.shader.set_uniform_i32("scene", 0);
+        self.shader.set_uniform_2_f32("offset", 1.0 / self.width as f32, 1.0 / self.height as f32);
+    }
+
+    unsafe fn check_status(gl : &glow::Context, what : &str) -> Result<(), ResourceError> {
+        let status = gl.check_framebuffer_status(glow::FRAMEBUFFER);
+        if status != glow::FRAMEBUFFER_COMPLETE {
+            gl.bind_framebuffer(glow::FRAMEBUFFER, None);
+            return Err(ResourceError::Framebuffer(format!("{} is not complete (status 0x{:x})", what, status)));
+        }
+        Ok(())
+    }
+
+    /// direct all drawing to the offscreen framebuffer and clear it
+    pub fn begin_render(&self) {
+        unsafe {
+            self.gl.bind_framebuffer(glow::FRAMEBUFFER, Some(self.msfbo.unwrap_or(self.fbo)));
+            self.gl.viewport(0, 0, self.width as i32, self.height as i32);
+            self.gl.clear_color(0.0, 0.0, 0.0, 1.0);
+            self.gl.clear(glow::COLOR_BUFFER_BIT);
+        }
+    }
+
+    /// resolve the multisampled scene into the texture and switch back to
+    /// the default framebuffer
+    pub fn end_render(&self) {
+        unsafe {
+            if let Some(msfbo) = self.msfbo {
+                self.gl.bind_framebuffer(glow::READ_FRAMEBUFFER, Some(msfbo));
+                self.gl.bind_framebuffer(glow::DRAW_FRAMEBUFFER, Some(self.fbo));
+                let (width, height) = (self.width as i32, self.height as i32);
+                self.gl.blit_framebuffer(0, 0, width, height, 0, 0, width, height, glow::COLOR_BUFFER_BIT, glow::NEAREST);
+            }
+            self.gl.bind_framebuffer(glow::FRAMEBUFFER, None);
+        }
+    }
+
+    /// draw the scene to the screen with the active effects, `time` drives
+    /// the shake and chaos animation
+    pub fn render(&self, time : f32) {
+        self.shader.use_program();
+        self.shader.set_uniform_f32("time", time);
+        self.shader.set_uniform_i32("confuse", self.confuse as i32);
+        self.shader.set_uniform_i32("chaos", self.chaos as i32);
+        self.shader.set_uniform_i32("shake", self.shake as i32);
+
+        unsafe {
+            self.gl.active_texture(glow::TEXTURE0);
+            self.gl.bind_texture(glow::TEXTURE_2D, Some(self.texture));
+            self.gl.bind_vertex_array(Some(self.vao));
+            self.gl.draw_arrays(glow::TRIANGLES, 0, 6);
+            self.gl.bind_vertex_array(None);
+        }
+    }
+}
+
+impl Drop for PostProcessor {
+    fn drop(&mut self) {
+        unsafe {
+            if let Some(msfbo) = self.msfbo {
+                self.gl.delete_framebuffer(msfbo);
+            }
+            if let Some(rbo) = self.rbo {
+                self.gl.delete_renderbuffer(rbo);
+            }
+            self.gl.delete_framebuffer(self.fbo);
+            self.gl.delete_texture(self.texture);
+            self.gl.delete_vertex_array(self.vao);
+            self.gl.delete_buffer(self.vbo);
+        }
+    }
+}
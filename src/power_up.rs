@@ -0,0 +1,114 @@
+use crate::game_object::GameObject;
+use crate::sprite::SpriteId;
+
+// Size of a falling pickup
+const POWER_UP_SIZE : glm::Vec2 = glm::Vec2::new(60.0, 20.0);
+// Falling speed of a pickup
+const POWER_UP_VELOCITY : glm::Vec2 = glm::Vec2::new(0.0, 150.0);
+
+/// Effect a power-up has once the paddle catches it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PowerUpKind {
+    /// the ball moves faster
+    Speed,
+    /// the ball sticks to the paddle until it is released again
+    Sticky,
+    /// the ball goes through the bricks it destroys
+    PassThrough,
+    /// the paddle gets wider
+    PaddleGrow,
+    /// the screen is drawn upside down and inverted
+    Confuse,
+    /// the screen swirls around
+    Chaos,
+    /// the ball splits into several balls
+    MultiBall,
+}
+
+impl PowerUpKind {
+    pub const ALL : [PowerUpKind; 7] = [
+        PowerUpKind::Speed,
+        PowerUpKind::Sticky,
+        PowerUpKind::PassThrough,
+        PowerUpKind::PaddleGrow,
+        PowerUpKind::Confuse,
+        PowerUpKind::Chaos,
+        PowerUpKind::MultiBall,
+    ];
+
+    /// how long the effect lasts in seconds, 0 for effects that happen once
+    pub fn duration(&self) -> f32 {
+        match self {
+            PowerUpKind::Speed => 10.0,
+            PowerUpKind::Sticky => 20.0,
+            PowerUpKind::PassThrough => 10.0,
+            PowerUpKind::PaddleGrow => 10.0,
+            PowerUpKind::Confuse => 15.0,
+            PowerUpKind::Chaos => 15.0,
+            PowerUpKind::MultiBall => 0.0,
+        }
+    }
+
+    /// colour of the falling pickup
+    pub fn color(&self) -> glm::Vec3 {
+        match self {
+            PowerUpKind::Speed => glm::vec3(0.5, 0.5, 1.0),
+            PowerUpKind::Sticky => glm::vec3(1.0, 0.5, 1.0),
+            PowerUpKind::PassThrough => glm::vec3(0.5, 1.0, 0.5),
+            PowerUpKind::PaddleGrow => glm::vec3(1.0, 0.6, 0.4),
+            PowerUpKind::Confuse => glm::vec3(1.0, 0.3, 0.3),
+            PowerUpKind::Chaos => glm::vec3(0.9, 0.25, 0.25),
+            PowerUpKind::MultiBall => glm::vec3(1.0, 1.0, 0.4),
+        }
+    }
+}
+
+/// A pickup falling from a destroyed brick
+#[derive(Debug)]
+pub struct PowerUp {
+    game_object : GameObject,
+    kind : PowerUpKind,
+}
+
+impl PowerUp {
+    /// pickup falling from the centre of `position`
+    pub fn new(kind : PowerUpKind, position : glm::Vec2, sprite : SpriteId) -> Self {
+        Self {
+            game_object : GameObject::new(
+                position - POWER_UP_SIZE.scale(0.5),
+                POWER_UP_SIZE,
+                POWER_UP_VELOCITY,
+                kind.color(),
+                0.0,
+                sprite,
+                false),
+            kind,
+        }
+    }
+
+    pub fn kind(&self) -> PowerUpKind {
+        self.kind
+    }
+
+    pub fn game_object(&self) -> &GameObject {
+        &self.game_object
+    }
+
+    /// remember the current position as the start of the next update
+    pub fn save_position(&mut self) {
+        self.game_object.save_position();
+    }
+
+    pub fn do_move(&mut self, dt : f32) {
+        let position = self.game_object.get_position() + self.game_object.get_velocity().scale(dt);
+        self.game_object.set_position(position);
+    }
+}
+
+/// A power-up effect that is currently running
+#[derive(Debug, Clone, Copy)]
+pub struct ActivePowerUp {
+    pub kind : PowerUpKind,
+    /// seconds left before the effect ends
+    pub remaining : f32,
+}
@@ -0,0 +1,313 @@
+//! Render regression tests. They draw into an offscreen OpenGL context of a
+//! software rasteriser (Mesa llvmpipe through EGL) and compare the result
+//! with the images in `tests/golden`.
+//!
+//! Run with `UPDATE_GOLDEN=1 cargo test` to write new golden images after an
+//! intended change. A mismatch writes the rendered image and a diff image,
+//! with the differing pixels in red, to `target/golden`. Without EGL the
+//! tests fail, set `SKIP_RENDER_TESTS=1` to skip them on such machines.
+
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::ffi::{c_char, c_void, CStr};
+use glow::HasContext;
+use glutin::event::{ElementState, VirtualKeyCode};
+use image::RgbaImage;
+use khronos_egl as egl;
+
+use crate::game::Game;
+use crate::game_renderer::GameRenderer;
+use crate::resource_manager::ResourceManager;
+use crate::sprite_renderer::SpriteRenderer;
+use crate::texture::Texture;
+
+const WIDTH : u32 = 800;
+const HEIGHT : u32 = 600;
+// a channel may differ this much before the pixel counts as different
+const CHANNEL_TOLERANCE : u8 = 8;
+// fraction of the pixels that may differ, rasterisers round differently
+const MAX_DIFFERENT_PIXELS : f64 = 0.001;
+// EGL_PLATFORM_DEVICE_EXT, renders on one device without a display server
+const PLATFORM_DEVICE : egl::Enum = 0x313F;
+// extension of the Mesa device that rasterises on the CPU
+const SOFTWARE_DEVICE : &str = "EGL_MESA_device_software";
+
+type QueryDevices = extern "system" fn(egl::Int, *mut *mut c_void, *mut egl::Int) -> egl::Boolean;
+type QueryDeviceString = extern "system" fn(*mut c_void, egl::Int) -> *const c_char;
+
+/// An OpenGL 3.3 core context that draws into a pbuffer
+struct OffscreenContext {
+    egl : egl::DynamicInstance<egl::EGL1_5>,
+    display : egl::Display,
+    context : egl::Context,
+    surface : egl::Surface,
+    gl : Rc<glow::Context>,
+}
+
+impl OffscreenContext {
+    /// `None` when SKIP_RENDER_TESTS is set, panics when there is no EGL
+    /// with a software rasteriser
+    fn new(width : u32, height : u32) -> Option<Self> {
+        if std::env::var_os("SKIP_RENDER_TESTS").is_some_and(|skip| skip != "0") {
+            eprintln!("skipping render test, SKIP_RENDER_TESTS is set");
+            return None;
+        }
+        match Self::create(width, height) {
+            Ok(context) => Some(context),
+            Err(err) => panic!("no offscreen OpenGL context: {}, set SKIP_RENDER_TESTS=1 to skip the render tests", err),
+        }
+    }
+
+    fn create(width : u32, height : u32) -> Result<Self, String> {
+        let egl = unsafe { egl::DynamicInstance::<egl::EGL1_5>::load_required() }
+            .map_err(|err| err.to_string())?;
+        // the same pixels on every machine, whatever GPU it has
+        let device = Self::software_device(&egl)?;
+        let display = unsafe { egl.get_platform_display(PLATFORM_DEVICE, device, &[egl::ATTRIB_NONE]) }
+            .map_err(|err| err.to_string())?;
+        egl.initialize(display).map_err(|err| err.to_string())?;
+        egl.bind_api(egl::OPENGL_API).map_err(|err| err.to_string())?;
+
+        let config = egl.choose_first_config(display, &[
+            egl::SURFACE_TYPE, egl::PBUFFER_BIT,
+            egl::RENDERABLE_TYPE, egl::OPENGL_BIT,
+            egl::RED_SIZE, 8, egl::GREEN_SIZE, 8, egl::BLUE_SIZE, 8, egl::ALPHA_SIZE, 8,
+            egl::NONE,
+        ]).map_err(|err| err.to_string())?.ok_or("no pbuffer config")?;
+        let context = egl.create_context(display, config, None, &[
+            egl::CONTEXT_MAJOR_VERSION, 3,
+            egl::CONTEXT_MINOR_VERSION, 3,
+            egl::CONTEXT_OPENGL_PROFILE_MASK, egl::CONTEXT_OPENGL_CORE_PROFILE_BIT,
+            egl::NONE,
+        ]).map_err(|err| err.to_string())?;
+        let surface = egl.create_pbuffer_surface(display, config, &[
+            egl::WIDTH, width as egl::Int,
+            egl::HEIGHT, height as egl::Int,
+            egl::NONE,
+        ]).map_err(|err| err.to_string())?;
+        egl.make_current(display, Some(surface), Some(surface), Some(context)).map_err(|err| err.to_string())?;
+
+        let gl = unsafe {
+            glow::Context::from_loader_function(|name| {
+                egl.get_proc_address(name).map_or(std::ptr::null(), |address| address as *const _)
+            })
+        };
+        unsafe {
+            gl.viewport(0, 0, width as i32, height as i32);
+            gl.enable(glow::BLEND);
+            gl.blend_func(glow::SRC_ALPHA, glow::ONE_MINUS_SRC_ALPHA);
+            gl.clear_color(0.0, 0.0, 0.0, 1.0);
+            gl.clear(glow::COLOR_BUFFER_BIT);
+        }
+
+        Ok(Self {
+            egl,
+            display,
+            context,
+            surface,
+            gl : Rc::new(gl),
+        })
+    }
+
+    /// the EGL device of the software rasteriser, found through the
+    /// EGL_EXT_device_enumeration and EGL_EXT_device_query extensions
+    fn software_device(egl : &egl::DynamicInstance<egl::EGL1_5>) -> Result<*mut c_void, String> {
+        let query_devices = egl.get_proc_address("eglQueryDevicesEXT").ok_or("no eglQueryDevicesEXT")?;
+        let query_device_string = egl.get_proc_address("eglQueryDeviceStringEXT").ok_or("no eglQueryDeviceStringEXT")?;
+        let (query_devices, query_device_string) = unsafe {(
+            std::mem::transmute::<extern "system" fn(), QueryDevices>(query_devices),
+            std::mem::transmute::<extern "system" fn(), QueryDeviceString>(query_device_string),
+        )};
+
+        let mut count = 0;
+        if query_devices(0, std::ptr::null_mut(), &mut count) != egl::TRUE {
+            return Err("cannot count the EGL devices".into());
+        }
+        let mut devices = vec![std::ptr::null_mut(); count as usize];
+        if query_devices(count, devices.as_mut_ptr(), &mut count) != egl::TRUE {
+            return Err("cannot list the EGL devices".into());
+        }
+        devices.truncate(count as usize);
+        devices.into_iter()
+            .find(|device| {
+                let extensions = query_device_string(*device, egl::EXTENSIONS);
+                !extensions.is_null() && unsafe { CStr::from_ptr(extensions) }.to_string_lossy()
+                    .split(' ')
+                    .any(|extension| extension == SOFTWARE_DEVICE)
+            })
+            .ok_or_else(|| format!("no EGL device with {}", SOFTWARE_DEVICE))
+    }
+
+    /// the pixels of the default framebuffer, top row first
+    fn read_pixels(&self, width : u32, height : u32) -> RgbaImage {
+        let mut pixels = vec![0u8; (width * height * 4) as usize];
+        unsafe {
+            self.gl.finish();
+            self.gl.read_pixels(0, 0, width as i32, height as i32, glow::RGBA, glow::UNSIGNED_BYTE, glow::PixelPackData::Slice(&mut pixels));
+        }
+        let image = RgbaImage::from_raw(width, height, pixels).expect("pixel buffer has the image size");
+        // OpenGL reads from the bottom row up
+        image::imageops::flip_vertical(&image)
+    }
+}
+
+impl Drop for OffscreenContext {
+    fn drop(&mut self) {
+        let _ = self.egl.make_current(self.display, None, None, None);
+        let _ = self.egl.destroy_surface(self.display, self.surface);
+        let _ = self.egl.destroy_context(self.display, self.context);
+    }
+}
+
+/// compare with the golden image `name`, or write it when UPDATE_GOLDEN is set
+fn assert_golden(name : &str, actual : &RgbaImage) {
+    let root = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let golden_path = root.join("tests").join("golden").join(format!("{}.png", name));
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        std::fs::create_dir_all(golden_path.parent().unwrap()).unwrap();
+        actual.save(&golden_path).unwrap();
+        return;
+    }
+
+    let expected = image::open(&golden_path)
+        .unwrap_or_else(|err| panic!("cannot read golden image {}: {}, run with UPDATE_GOLDEN=1 to create it", golden_path.display(), err))
+        .to_rgba8();
+    assert_eq!(expected.dimensions(), actual.dimensions(), "size of {} differs from the golden image", name);
+
+    let mut diff = RgbaImage::new(actual.width(), actual.height());
+    let mut different = 0;
+    for ((expected, actual), diff) in expected.pixels().zip(actual.pixels()).zip(diff.pixels_mut()) {
+        let distance = expected.0.iter().zip(actual.0.iter()).map(|(a, b)| a.abs_diff(*b)).max().unwrap_or(0);
+        *diff = if distance > CHANNEL_TOLERANCE {
+            different += 1;
+            image::Rgba([255, 0, 0, 255])
+        } else {
+            // the expected image, dimmed, so the red pixels can be placed
+            let [r, g, b, _] = expected.0;
+            image::Rgba([r / 3, g / 3, b / 3, 255])
+        };
+    }
+
+    let total = (actual.width() * actual.height()) as f64;
+    if different as f64 > total * MAX_DIFFERENT_PIXELS {
+        let output = root.join("target").join("golden");
+        std::fs::create_dir_all(&output).unwrap();
+        let actual_path = output.join(format!("{}.actual.png", name));
+        let diff_path = output.join(format!("{}.diff.png", name));
+        actual.save(&actual_path).unwrap();
+        diff.save(&diff_path).unwrap();
+        panic!("{} differs from the golden image in {} pixels, see {} and {}",
+            name, different, actual_path.display(), diff_path.display());
+    }
+}
+
+fn press(game : &mut Game, key : VirtualKeyCode) {
+    game.process_key(key, ElementState::Pressed);
+    game.process_input();
+    game.process_key(key, ElementState::Released);
+}
+
+/// render the game after it ran `ticks` updates, with level `level`
+/// selected in the menu and started when `start` is set
+fn render_game(name : &str, level : usize, start : bool, ticks : u32) {
+    let context = match OffscreenContext::new(WIDTH, HEIGHT) {
+        Some(context) => context,
+        None => return,
+    };
+
+    let mut game = Game::new(WIDTH, HEIGHT);
+    game.init().unwrap();
+    let resource_manager = Rc::new(ResourceManager::new(context.gl.clone()));
+    let mut renderer = GameRenderer::new(resource_manager, WIDTH, HEIGHT).unwrap();
+    renderer.load_sprites(&game).unwrap();
+
+    for _ in 0..level {
+        press(&mut game, VirtualKeyCode::Down);
+    }
+    if start {
+        press(&mut game, VirtualKeyCode::Return);
+        press(&mut game, VirtualKeyCode::Space);
+    }
+    for _ in 0..ticks {
+        game.update(1.0 / 120.0);
+    }
+
+    renderer.render(&game, 1.0);
+    assert_golden(name, &context.read_pixels(WIDTH, HEIGHT));
+}
+
+#[test]
+fn menu() {
+    render_game("menu", 0, false, 0);
+}
+
+#[test]
+fn level_one_playing() {
+    render_game("level_one_playing", 0, true, 120);
+}
+
+#[test]
+fn toml_level_playing() {
+    // the fifth level, one step down from the first
+    render_game("toml_level_playing", 1, true, 240);
+}
+
+#[test]
+fn sprite_renderer_transform() {
+    let context = match OffscreenContext::new(WIDTH, HEIGHT) {
+        Some(context) => context,
+        None => return,
+    };
+    let resource_manager = ResourceManager::new(context.gl.clone());
+    let shader = resource_manager.load_shader("src/shaders/sprite.vs", "src/shaders/sprite.fs", "sprite".into()).unwrap();
+    shader.use_program();
+    shader.set_uniform_i32("image", 0);
+    shader.set_uniform_mat4("projection", &glm::ortho(0.0, WIDTH as f32, HEIGHT as f32, 0.0, -1.0, 1.0));
+    let white = Texture::new(context.gl.clone(), 1, 1, vec![255; 4]).unwrap();
+    let renderer = SpriteRenderer::new(context.gl.clone(), shader);
+
+    // a red 200 x 100 sprite and a green one turned a quarter around its centre
+    renderer.begin();
+    renderer.draw(&white, glm::vec2(100.0, 50.0), glm::vec2(200.0, 100.0), 0.0, glm::vec3(1.0, 0.0, 0.0));
+    renderer.draw(&white, glm::vec2(400.0, 300.0), glm::vec2(200.0, 100.0), 90.0, glm::vec3(0.0, 1.0, 0.0));
+    renderer.flush();
+
+    let image = context.read_pixels(WIDTH, HEIGHT);
+    let red = image::Rgba([255, 0, 0, 255]);
+    let green = image::Rgba([0, 255, 0, 255]);
+    let black = image::Rgba([0, 0, 0, 255]);
+    // y grows downwards, like in the game
+    assert_eq!(*image.get_pixel(101, 51), red);
+    assert_eq!(*image.get_pixel(298, 148), red);
+    assert_eq!(*image.get_pixel(99, 51), black);
+    assert_eq!(*image.get_pixel(101, 151), black);
+    // turned, it covers 450..550 by 250..450
+    assert_eq!(*image.get_pixel(455, 255), green);
+    assert_eq!(*image.get_pixel(545, 445), green);
+    assert_eq!(*image.get_pixel(405, 305), black);
+    assert_eq!(*image.get_pixel(595, 345), black);
+}
+
+#[test]
+fn failed_shader_reload_keeps_the_program() {
+    let context = match OffscreenContext::new(WIDTH, HEIGHT) {
+        Some(context) => context,
+        None => return,
+    };
+    let resource_manager = ResourceManager::new(context.gl.clone());
+    let shader = resource_manager.load_shader("src/shaders/sprite.vs", "src/shaders/sprite.fs", "sprite".into()).unwrap();
+    let vertex_source = std::fs::read_to_string(concat!(env!("CARGO_MANIFEST_DIR"), "/src/shaders/sprite.vs")).unwrap();
+    assert!(shader.reload(&vertex_source, "#version 330 core\nnot a shader").is_err());
+
+    shader.use_program();
+    shader.set_uniform_i32("image", 0);
+    shader.set_uniform_mat4("projection", &glm::ortho(0.0, WIDTH as f32, HEIGHT as f32, 0.0, -1.0, 1.0));
+    let white = Texture::new(context.gl.clone(), 1, 1, vec![255; 4]).unwrap();
+    let renderer = SpriteRenderer::new(context.gl.clone(), shader);
+    renderer.begin();
+    renderer.draw(&white, glm::vec2(100.0, 50.0), glm::vec2(200.0, 100.0), 0.0, glm::vec3(1.0, 0.0, 0.0));
+    renderer.flush();
+
+    let image = context.read_pixels(WIDTH, HEIGHT);
+    assert_eq!(*image.get_pixel(150, 100), image::Rgba([255, 0, 0, 255]));
+}
@@ -0,0 +1,341 @@
+use std::hash::Hasher;
+use std::path::Path;
+use glutin::event::{ElementState, MouseButton, VirtualKeyCode};
+use serde::{Deserialize, Serialize};
+
+use crate::error::{ReplayError, ResourceError};
+use crate::game::Game;
+use crate::high_scores::HighScores;
+
+// bumped whenever the file layout or the simulation changes in a way that
+// makes older replays desync
+const REPLAY_VERSION : u32 = 1;
+
+/// One input given to the game
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum InputEvent {
+    Key(VirtualKeyCode, ElementState),
+    Char(char),
+    MouseButton(MouseButton, ElementState),
+    /// cursor in logical playfield coordinates, `None` when it left the window
+    Pointer(Option<[f32; 2]>),
+    ReleaseKeys,
+}
+
+impl InputEvent {
+    /// feed the event into the game
+    pub fn apply(&self, game : &mut Game) {
+        match *self {
+            InputEvent::Key(key, state) => game.process_key(key, state),
+            InputEvent::Char(c) => game.process_char(c),
+            InputEvent::MouseButton(button, state) => game.process_mouse_button(button, state),
+            InputEvent::Pointer(position) => game.process_pointer(position.map(|[x, y]| glm::vec2(x, y))),
+            InputEvent::ReleaseKeys => game.release_keys(),
+        }
+    }
+}
+
+/// The events handled by one call to `Game::process_input`, made before
+/// simulation tick `tick`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InputFrame {
+    pub tick : u64,
+    pub events : Vec<InputEvent>,
+}
+
+/// Everything needed to play a game again exactly: the state the game
+/// started from, all input and the checksum of the state it ended in
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Replay {
+    version : u32,
+    /// seconds per simulation tick
+    step : f32,
+    /// the high score table decides whether a name is asked for
+    high_scores : HighScores,
+    frames : Vec<InputFrame>,
+    ticks : u64,
+    checksum : u64,
+}
+
+impl Replay {
+    /// read a replay written by `save`
+    pub fn load(path : &Path) -> Result<Self, ResourceError> {
+        let data = std::fs::read(path)
+            .map_err(|source| ResourceError::Io { path : path.display().to_string(), source })?;
+        let replay : Self = bincode::deserialize(&data)
+            .map_err(|err| ResourceError::Replay { path : path.display().to_string(), message : err.to_string() })?;
+        if replay.version != REPLAY_VERSION {
+            return Err(ResourceError::Replay {
+                path : path.display().to_string(),
+                message : format!("version {} is not supported, expected {}", replay.version, REPLAY_VERSION),
+            });
+        }
+        Ok(replay)
+    }
+
+    pub fn save(&self, path : &Path) -> Result<(), ResourceError> {
+        let data = bincode::serialize(self)
+            .map_err(|err| ResourceError::Replay { path : path.display().to_string(), message : err.to_string() })?;
+        std::fs::write(path, data)
+            .map_err(|source| ResourceError::Write { path : path.display().to_string(), source })
+    }
+
+    pub fn step(&self) -> f32 {
+        self.step
+    }
+
+    /// number of simulation ticks recorded
+    pub fn ticks(&self) -> u64 {
+        self.ticks
+    }
+
+    pub fn checksum(&self) -> u64 {
+        self.checksum
+    }
+
+    /// run the whole replay on a freshly initialised game without a window,
+    /// returns the checksum of the final state
+    pub fn verify(&self, game : &mut Game) -> Result<u64, ReplayError> {
+        let mut player = ReplayPlayer::new(self.clone(), game);
+        while !player.is_finished(game) {
+            player.tick(game);
+        }
+        player.finish(game)
+    }
+}
+
+/// Logs the input fed into a game, call `record_frame` with the events
+/// given to the game right before each `Game::process_input`
+pub struct Recorder {
+    replay : Replay,
+}
+
+impl Recorder {
+    /// start recording a game that was just initialised and runs ticks of
+    /// `step` seconds
+    pub fn new(game : &Game, step : f32) -> Self {
+        Self {
+            replay : Replay {
+                version : REPLAY_VERSION,
+                step,
+                high_scores : game.high_scores().clone(),
+                frames : Vec::new(),
+                ticks : 0,
+                checksum : 0,
+            },
+        }
+    }
+
+    /// the events are handled by the game before tick `tick`, frames
+    /// without events change nothing and are left out
+    pub fn record_frame(&mut self, tick : u64, events : &[InputEvent]) {
+        if !events.is_empty() {
+            self.replay.frames.push(InputFrame {
+                tick,
+                events : events.to_vec(),
+            });
+        }
+    }
+
+    /// stop recording, the game is in the state the replay has to end in
+    pub fn finish(mut self, game : &Game) -> Replay {
+        self.replay.ticks = game.ticks();
+        self.replay.checksum = game.checksum();
+        self.replay
+    }
+}
+
+/// Feeds a replay back into a game one tick at a time, so it can be watched
+pub struct ReplayPlayer {
+    replay : Replay,
+    next_frame : usize,
+}
+
+impl ReplayPlayer {
+    /// prepare a freshly initialised game to play the replay
+    pub fn new(replay : Replay, game : &mut Game) -> Self {
+        game.set_high_scores(replay.high_scores.clone());
+        Self {
+            replay,
+            next_frame : 0,
+        }
+    }
+
+    pub fn replay(&self) -> &Replay {
+        &self.replay
+    }
+
+    /// all recorded ticks have been played
+    pub fn is_finished(&self, game : &Game) -> bool {
+        game.ticks() >= self.replay.ticks
+    }
+
+    /// handle the input recorded for the current tick and run it
+    pub fn tick(&mut self, game : &mut Game) {
+        if self.is_finished(game) {
+            return;
+        }
+        self.apply_frames(game);
+        game.update(self.replay.step);
+    }
+
+    /// play the input recorded after the last tick and compare the final
+    /// state with the recording
+    pub fn finish(&mut self, game : &mut Game) -> Result<u64, ReplayError> {
+        self.apply_frames(game);
+        let checksum = game.checksum();
+        if checksum != self.replay.checksum {
+            return Err(ReplayError::Desync { expected : self.replay.checksum, actual : checksum });
+        }
+        Ok(checksum)
+    }
+
+    fn apply_frames(&mut self, game : &mut Game) {
+        while let Some(frame) = self.replay.frames.get(self.next_frame) {
+            if frame.tick > game.ticks() {
+                break;
+            }
+            frame.events.iter().for_each(|event| event.apply(game));
+            game.process_input();
+            self.next_frame += 1;
+        }
+    }
+}
+
+/// 64 bit FNV-1a, unlike the standard hasher its output is stable across
+/// Rust versions, so checksums can be kept in files
+#[derive(Debug, Clone)]
+pub struct StateHasher(u64);
+
+impl Default for StateHasher {
+    fn default() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl StateHasher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn write_f32(&mut self, value : f32) {
+        self.write_u32(value.to_bits());
+    }
+
+    pub fn write_vec2(&mut self, value : glm::Vec2) {
+        self.write_f32(value.x);
+        self.write_f32(value.y);
+    }
+}
+
+impl Hasher for StateHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes : &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    }
+
+    // the same bytes on every platform
+
+    fn write_u32(&mut self, value : u32) {
+        self.write(&value.to_le_bytes());
+    }
+
+    fn write_u64(&mut self, value : u64) {
+        self.write(&value.to_le_bytes());
+    }
+
+    fn write_usize(&mut self, value : usize) {
+        self.write_u64(value as u64);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    const STEP : f32 = 1.0 / 120.0;
+    const TICKS : u64 = 600;
+
+    fn new_game() -> Game {
+        let mut game = Game::new(800, 600);
+        game.init().unwrap();
+        game
+    }
+
+    fn key(key : VirtualKeyCode, state : ElementState) -> InputEvent {
+        InputEvent::Key(key, state)
+    }
+
+    /// start a level, move the paddle and launch the ball while recording
+    fn record() -> (Game, Replay) {
+        let script = [
+            (0, key(VirtualKeyCode::Return, ElementState::Pressed)),
+            (1, key(VirtualKeyCode::Return, ElementState::Released)),
+            (20, key(VirtualKeyCode::Left, ElementState::Pressed)),
+            (60, key(VirtualKeyCode::Left, ElementState::Released)),
+            (61, key(VirtualKeyCode::Space, ElementState::Pressed)),
+            (62, key(VirtualKeyCode::Space, ElementState::Released)),
+            (200, InputEvent::Pointer(Some([500.0, 550.0]))),
+        ];
+
+        let mut game = new_game();
+        let mut recorder = Recorder::new(&game, STEP);
+        for tick in 0..TICKS {
+            let events : Vec<InputEvent> = script.iter()
+                .filter(|(at, _)| *at == tick)
+                .map(|(_, event)| *event)
+                .collect();
+            recorder.record_frame(game.ticks(), &events);
+            events.iter().for_each(|event| event.apply(&mut game));
+            game.process_input();
+            game.update(STEP);
+        }
+        let replay = recorder.finish(&game);
+        (game, replay)
+    }
+
+    #[test]
+    fn replay_ends_with_the_recorded_checksum() {
+        let (game, replay) = record();
+        assert_eq!(replay.ticks(), TICKS);
+        assert_eq!(replay.checksum(), game.checksum());
+        assert_eq!(replay.verify(&mut new_game()).unwrap(), game.checksum());
+    }
+
+    #[test]
+    fn saved_replay_loads_again() {
+        let (game, replay) = record();
+        let dir = TempDir::new("saved_replay");
+        let path = dir.path().join("game.replay");
+        replay.save(&path).unwrap();
+
+        let loaded = Replay::load(&path).unwrap();
+        assert_eq!(loaded.frames, replay.frames);
+        assert_eq!(loaded.ticks(), replay.ticks());
+        assert_eq!(loaded.verify(&mut new_game()).unwrap(), game.checksum());
+    }
+
+    #[test]
+    fn changed_input_desyncs() {
+        let (_, mut replay) = record();
+        let launch = replay.frames.iter_mut()
+            .find(|frame| frame.events.contains(&key(VirtualKeyCode::Space, ElementState::Pressed)))
+            .unwrap();
+        launch.events[0] = key(VirtualKeyCode::Right, ElementState::Pressed);
+
+        match replay.verify(&mut new_game()) {
+            Err(ReplayError::Desync { expected, actual }) => {
+                assert_eq!(expected, replay.checksum());
+                assert_ne!(actual, expected);
+            },
+            other => panic!("expected a desync, got {:?}", other),
+        }
+    }
+}
@@ -1,71 +1,264 @@
-/*******************************************************************
-** This code is part of Breakout.
-**
-** Breakout is free software: you can redistribute it and/or modify
-** it under the terms of the CC BY 4.0 license as published by
-** Creative Commons, either version 4 of the License, or (at your
-** option) any later version.
-******************************************************************/
-
-use std::{rc::Rc};
-use std::cell::RefCell;
-use std::collections::HashMap;
-use super::texture::Texture;
-use super::shader::Shader;
-
-pub struct ResourceManager{
-    gl : Rc<glow::Context>,    
-    texture_pool : RefCell< HashMap<String, Rc<Texture>>>,
-    shader_pool : RefCell< HashMap<String, Rc<Shader>>>,
-}
-
-impl ResourceManager {
-
-    pub fn new(gl : Rc<glow::Context>) -> Self {
-        Self {
-            gl,
-            texture_pool : RefCell::new( HashMap::new()),
-            shader_pool : RefCell::new( HashMap::new()),
-        }
-    }
-
-    pub fn get_gl(&self) -> Rc<glow::Context> {
-        self.gl.clone()
-    }
-
-    pub fn load_texture( &self,  img_file_name :&str, name: String ) ->Rc<Texture> {
-
-        if let Some(rc_texture) = self.texture_pool.borrow().get(&name) {
-            return rc_texture.clone();
-        }
-
-        println!("Loading image: {}", img_file_name);
-        //let img = image::open(img_file_name).unwrap().flipv().into_rgba8();
-        let img = image::open(img_file_name).unwrap().into_rgba8();
-
-        let (img_w, img_h) = img.dimensions();
-        let raw_img = img.into_raw();
-
-        let rc_texture = Rc::new( Texture::new(self.gl.clone(), img_w, img_h, raw_img) );
-        self.texture_pool.borrow_mut().insert(name, rc_texture.clone());
-        println!("Texture inserted into pool .. ");
-        rc_texture
-    }
-    
-    pub fn load_shader( &self, vx_shader_path:&str, fg_shader_path:&str, name: String )-> Rc<Shader> {
-
-        let vx_shader = std::fs::read_to_string(vx_shader_path).expect(format!("Failed to read file: {}",vx_shader_path).as_str());
-        let fg_shader = std::fs::read_to_string(fg_shader_path).expect(format!("Failed to read file: {}",fg_shader_path).as_str());
-        let rc_shader = Rc::new( Shader::new(self.gl.clone(), vx_shader.as_str(), fg_shader.as_str()));
-        self.shader_pool.borrow_mut().insert(name, rc_shader.clone() );
-        rc_shader
-    }
-
-    pub fn get_texture(&self, name: String)-> Rc<Texture> {
-        self.texture_pool.borrow().get(&name).unwrap().clone()
-    }    
-
-    pub fn get_shader(&self, name: String)-> Rc<Shader> {
-        self.shader_pool.borrow().get(&name).unwrap().clone()
-    }    
-}
\ No newline at end of file
+/*******************************************************************
+** This code is part of Breakout.
+**
+** Breakout is free software: you can redistribute it and/or modify
+** it under the terms of the CC BY 4.0 license as published by
+** Creative Commons, either version 4 of the License, or (at your
+** option) any later version.
+******************************************************************/
+
+use std::{rc::Rc};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use super::texture::Texture;
+use super::texture_atlas::TextureAtlas;
+use super::text_renderer::Font;
+use super::shader::Shader;
+use super::assets::Assets;
+use super::error::ResourceError;
+
+pub struct ResourceManager{
+    gl : Rc<glow::Context>,    
+    assets : Rc<Assets>,
+    texture_pool : RefCell< HashMap<String, Rc<Texture>>>,
+    shader_pool : RefCell< HashMap<String, Rc<Shader>>>,
+    atlas_pool : RefCell< HashMap<String, Rc<TextureAtlas>>>,
+    font_pool : RefCell< HashMap<String, Rc<Font>>>,
+    // files the resources were made from, so they can be reloaded
+    shader_sources : RefCell< HashMap<String, (String, String)>>,
+    texture_sources : RefCell< HashMap<String, String>>,
+    atlas_sources : RefCell< HashMap<String, Vec<(String, String)>>>,
+}
+
+impl ResourceManager {
+
+    /// a resource manager that loads the assets built into the binary
+    pub fn new(gl : Rc<glow::Context>) -> Self {
+        Self::with_assets(gl, Rc::new(Assets::embedded()))
+    }
+
+    pub fn with_assets(gl : Rc<glow::Context>, assets : Rc<Assets>) -> Self {
+        Self {
+            gl,
+            assets,
+            texture_pool : RefCell::new( HashMap::new()),
+            shader_pool : RefCell::new( HashMap::new()),
+            atlas_pool : RefCell::new( HashMap::new()),
+            font_pool : RefCell::new( HashMap::new()),
+            shader_sources : RefCell::new( HashMap::new()),
+            texture_sources : RefCell::new( HashMap::new()),
+            atlas_sources : RefCell::new( HashMap::new()),
+        }
+    }
+
+    pub fn get_gl(&self) -> Rc<glow::Context> {
+        self.gl.clone()
+    }
+
+    /// the assets all resources are loaded from
+    pub fn assets(&self) -> Rc<Assets> {
+        self.assets.clone()
+    }
+
+    pub fn load_texture( &self,  img_file_name :&str, name: String ) -> Result<Rc<Texture>, ResourceError> {
+
+        if let Some(rc_texture) = self.texture_pool.borrow().get(&name) {
+            return Ok(rc_texture.clone());
+        }
+
+        let img = self.load_image(img_file_name)?;
+
+        let (img_w, img_h) = img.dimensions();
+        let raw_img = img.into_raw();
+
+        let texture = Texture::new(self.gl.clone(), img_w, img_h, raw_img)
+            .map_err(|message| ResourceError::Texture { path : img_file_name.into(), message })?;
+        let rc_texture = Rc::new( texture );
+        self.texture_sources.borrow_mut().insert(name.clone(), img_file_name.into());
+        self.texture_pool.borrow_mut().insert(name, rc_texture.clone());
+        Ok(rc_texture)
+    }
+    
+    /// pack a set of images into a single texture, `images` holds the name
+    /// and file of every image, the atlas hands out regions by that name
+    pub fn load_atlas( &self, images : &[(&str, &str)], name: String ) -> Result<Rc<TextureAtlas>, ResourceError> {
+
+        if let Some(rc_atlas) = self.atlas_pool.borrow().get(&name) {
+            return Ok(rc_atlas.clone());
+        }
+
+        let sources = images.iter()
+            .map(|(image_name, img_file_name)| ((*image_name).into(), (*img_file_name).into()))
+            .collect();
+        let images = images.iter()
+            .map(|(image_name, img_file_name)| Ok(((*image_name).into(), self.load_image(img_file_name)?)))
+            .collect::<Result<Vec<_>, ResourceError>>()?;
+
+        let rc_atlas = Rc::new( TextureAtlas::new(self.gl.clone(), &name, images)? );
+        self.atlas_sources.borrow_mut().insert(name.clone(), sources);
+        self.atlas_pool.borrow_mut().insert(name, rc_atlas.clone());
+        Ok(rc_atlas)
+    }
+
+    /// rasterise a TrueType font at `pixel_size` pixels
+    pub fn load_truetype_font( &self, font_file_name : &str, pixel_size : f32, name: String ) -> Result<Rc<Font>, ResourceError> {
+
+        if let Some(rc_font) = self.font_pool.borrow().get(&name) {
+            return Ok(rc_font.clone());
+        }
+
+        let data = self.assets.read(font_file_name)?;
+        let rc_font = Rc::new( Font::from_truetype(self.gl.clone(), &name, &data, pixel_size)? );
+        self.font_pool.borrow_mut().insert(name, rc_font.clone());
+        Ok(rc_font)
+    }
+
+    /// load a monospaced font from an image with a grid of characters
+    pub fn load_bitmap_font( &self, img_file_name : &str, cell_width : u32, cell_height : u32, name: String ) -> Result<Rc<Font>, ResourceError> {
+
+        if let Some(rc_font) = self.font_pool.borrow().get(&name) {
+            return Ok(rc_font.clone());
+        }
+
+        let img = self.load_image(img_file_name)?;
+        let rc_font = Rc::new( Font::from_bitmap(self.gl.clone(), &name, &img, cell_width, cell_height)? );
+        self.font_pool.borrow_mut().insert(name, rc_font.clone());
+        Ok(rc_font)
+    }
+
+    pub fn load_shader( &self, vx_shader_path:&str, fg_shader_path:&str, name: String ) -> Result<Rc<Shader>, ResourceError> {
+
+        let vx_shader = self.assets.read_to_string(vx_shader_path)?;
+        let fg_shader = self.assets.read_to_string(fg_shader_path)?;
+        let shader = Shader::new(self.gl.clone(), vx_shader.as_str(), fg_shader.as_str())
+            .map_err(|source| ResourceError::Shader {
+                name : name.clone(),
+                vertex_path : vx_shader_path.into(),
+                fragment_path : fg_shader_path.into(),
+                source,
+            })?;
+        let rc_shader = Rc::new( shader );
+        self.shader_sources.borrow_mut().insert(name.clone(), (vx_shader_path.into(), fg_shader_path.into()));
+        self.shader_pool.borrow_mut().insert(name, rc_shader.clone() );
+        Ok(rc_shader)
+    }
+
+    /// all assets shaders, textures and atlases were loaded from
+    pub fn watched_files(&self) -> Vec<String> {
+        let mut files = Vec::new();
+        for (vx_shader_path, fg_shader_path) in self.shader_sources.borrow().values() {
+            files.push(vx_shader_path.clone());
+            files.push(fg_shader_path.clone());
+        }
+        files.extend(self.texture_sources.borrow().values().cloned());
+        for sources in self.atlas_sources.borrow().values() {
+            files.extend(sources.iter().map(|(_, img_file_name)| img_file_name.clone()));
+        }
+        files.sort();
+        files.dedup();
+        files
+    }
+
+    /// load the resources made from any of the `changed` assets again. A
+    /// shader that fails to compile keeps its old program, errors are
+    /// reported and the other resources are still reloaded.
+    pub fn reload_changed(&self, changed : &[String]) {
+        let is_changed = |path : &str| changed.iter().any(|changed| changed == path);
+
+        for (name, (vx_shader_path, fg_shader_path)) in self.shader_sources.borrow().iter() {
+            if is_changed(vx_shader_path) || is_changed(fg_shader_path) {
+                report(self.reload_shader(name, vx_shader_path, fg_shader_path));
+            }
+        }
+        for (name, img_file_name) in self.texture_sources.borrow().iter() {
+            if is_changed(img_file_name) {
+                report(self.reload_texture(name, img_file_name));
+            }
+        }
+        for (name, sources) in self.atlas_sources.borrow().iter() {
+            for (image_name, img_file_name) in sources.iter() {
+                if is_changed(img_file_name) {
+                    report(self.reload_atlas_image(name, image_name, img_file_name));
+                }
+            }
+        }
+    }
+
+    fn reload_shader(&self, name : &str, vx_shader_path : &str, fg_shader_path : &str) -> Result<(), ResourceError> {
+        let shader = self.get_shader(name.into())?;
+        let vx_shader = self.assets.read_to_string(vx_shader_path)?;
+        let fg_shader = self.assets.read_to_string(fg_shader_path)?;
+        shader.reload(vx_shader.as_str(), fg_shader.as_str())
+            .map_err(|source| ResourceError::Shader {
+                name : name.into(),
+                vertex_path : vx_shader_path.into(),
+                fragment_path : fg_shader_path.into(),
+                source,
+            })?;
+        log::info!("Reloaded shader {}", name);
+        Ok(())
+    }
+
+    fn reload_texture(&self, name : &str, img_file_name : &str) -> Result<(), ResourceError> {
+        let texture = self.get_texture(name.into())?;
+        let img = self.load_image(img_file_name)?;
+        texture.update(img.width(), img.height(), img.as_raw());
+        Ok(())
+    }
+
+    fn reload_atlas_image(&self, name : &str, image_name : &str, img_file_name : &str) -> Result<(), ResourceError> {
+        let atlas = self.get_atlas(name.into())?;
+        atlas.replace_image(image_name, &self.load_image(img_file_name)?)
+    }
+
+    /// size of an image without decoding all of it
+    pub fn image_dimensions(&self, img_file_name : &str) -> Result<(u32, u32), ResourceError> {
+        let data = self.assets.read(img_file_name)?;
+        image::io::Reader::new(std::io::Cursor::new(data))
+            .with_guessed_format()
+            .map_err(|source| ResourceError::Io { path : img_file_name.into(), source })?
+            .into_dimensions()
+            .map_err(|source| image_error(img_file_name, source))
+    }
+
+    /// decode an image asset as 8 bit rgba
+    fn load_image(&self, img_file_name : &str) -> Result<image::RgbaImage, ResourceError> {
+        let data = self.assets.read(img_file_name)?;
+        let img = image::load_from_memory(&data)
+            .map_err(|source| image_error(img_file_name, source))?
+            .into_rgba8();
+        Ok(img)
+    }
+
+    pub fn get_texture(&self, name: String) -> Result<Rc<Texture>, ResourceError> {
+        self.texture_pool.borrow().get(&name).cloned().ok_or(ResourceError::MissingTexture(name))
+    }    
+
+    pub fn get_shader(&self, name: String) -> Result<Rc<Shader>, ResourceError> {
+        self.shader_pool.borrow().get(&name).cloned().ok_or(ResourceError::MissingShader(name))
+    }    
+
+    pub fn get_atlas(&self, name: String) -> Result<Rc<TextureAtlas>, ResourceError> {
+        self.atlas_pool.borrow().get(&name).cloned().ok_or(ResourceError::MissingAtlas(name))
+    }
+
+    pub fn get_font(&self, name: String) -> Result<Rc<Font>, ResourceError> {
+        self.font_pool.borrow().get(&name).cloned().ok_or(ResourceError::MissingFont(name))
+    }
+}
+
+/// a file that can not be read is reported as such, not as an image that
+/// can not be decoded
+fn image_error(img_file_name : &str, source : image::ImageError) -> ResourceError {
+    match source {
+        image::ImageError::IoError(source) => ResourceError::Io { path : img_file_name.into(), source },
+        source => ResourceError::Image { path : img_file_name.into(), source },
+    }
+}
+
+/// a resource that failed to reload keeps its old version
+fn report(result : Result<(), ResourceError>) {
+    if let Err(err) = result {
+        eprintln!("Error: {:#}", anyhow::Error::new(err));
+    }
+}
@@ -0,0 +1,89 @@
+// consecutive brick hits needed for every step of the combo multiplier
+const COMBO_STEP : u32 = 3;
+// highest combo multiplier
+const MAX_MULTIPLIER : u32 = 5;
+// points for every second a level is cleared before its par time
+const TIME_BONUS_PER_SECOND : u32 = 10;
+// par time of levels that do not set one, in seconds
+const DEFAULT_PAR_TIME : f32 = 120.0;
+
+/// Score of a level being played, with the combo of brick hits since the
+/// ball last touched the paddle and the time spent on the level
+#[derive(Debug, Clone, Default)]
+pub struct Scoring {
+    score : u32,
+    combo : u32,
+    level_time : f32,
+    bricks_destroyed : u32,
+    time_bonus : u32,
+}
+
+impl Scoring {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// start a level from zero
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    /// time passes while the level is played
+    pub fn update(&mut self, dt : f32) {
+        self.level_time += dt;
+    }
+
+    /// factor for the points of a destroyed brick, it grows with every few
+    /// consecutive hits
+    pub fn multiplier(&self) -> u32 {
+        (1 + self.combo / COMBO_STEP).min(MAX_MULTIPLIER)
+    }
+
+    /// the ball hit a breakable brick, returns the points scored
+    pub fn brick_hit(&mut self, points : u32, destroyed : bool) -> u32 {
+        self.combo += 1;
+        if !destroyed {
+            return 0;
+        }
+        self.bricks_destroyed += 1;
+        let points = points * self.multiplier();
+        self.score += points;
+        points
+    }
+
+    /// touching the paddle or losing the ball ends the combo
+    pub fn break_combo(&mut self) {
+        self.combo = 0;
+    }
+
+    /// add the bonus for clearing the level before `par_time`, returns it
+    pub fn level_cleared(&mut self, par_time : Option<f32>) -> u32 {
+        let seconds_left = (par_time.unwrap_or(DEFAULT_PAR_TIME) - self.level_time).max(0.0);
+        self.time_bonus = seconds_left as u32 * TIME_BONUS_PER_SECOND;
+        self.score += self.time_bonus;
+        self.time_bonus
+    }
+
+    pub fn score(&self) -> u32 {
+        self.score
+    }
+
+    /// brick hits since the ball last touched the paddle
+    pub fn combo(&self) -> u32 {
+        self.combo
+    }
+
+    /// seconds spent on the level
+    pub fn level_time(&self) -> f32 {
+        self.level_time
+    }
+
+    pub fn bricks_destroyed(&self) -> u32 {
+        self.bricks_destroyed
+    }
+
+    /// bonus of the last cleared level
+    pub fn time_bonus(&self) -> u32 {
+        self.time_bonus
+    }
+}
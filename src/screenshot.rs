@@ -0,0 +1,187 @@
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::thread::JoinHandle;
+use std::time::{SystemTime, UNIX_EPOCH};
+use glow::HasContext;
+use image::RgbaImage;
+
+use crate::error::ResourceError;
+
+// frames waiting to be written, rendering waits when the writer falls this
+// far behind
+const CAPTURE_QUEUE : usize = 8;
+
+/// read a part of the framebuffer that is bound for reading, `x` and `y`
+/// count from the bottom left like `glViewport`. The image is flipped so its
+/// first row is the top of the screen.
+pub fn read_pixels(gl : &glow::Context, x : i32, y : i32, width : i32, height : i32) -> RgbaImage {
+    let (width, height) = (width.max(0), height.max(0));
+    let mut pixels = vec![0u8; width as usize * height as usize * 4];
+    unsafe {
+        gl.pixel_store_i32(glow::PACK_ALIGNMENT, 1);
+        gl.read_pixels(x, y, width, height, glow::RGBA, glow::UNSIGNED_BYTE, glow::PixelPackData::Slice(&mut pixels));
+    }
+    let mut image = RgbaImage::from_raw(width as u32, height as u32, pixels)
+        .expect("pixel buffer has the image size");
+    image::imageops::flip_vertical_in_place(&mut image);
+    // the scene is opaque, but blending leaves other values in the alpha channel
+    image.pixels_mut().for_each(|pixel| pixel.0[3] = 255);
+    image
+}
+
+/// write the image as a PNG
+pub fn save_png(image : &RgbaImage, path : &Path) -> Result<(), ResourceError> {
+    image.save(path).map_err(|source| ResourceError::ImageWrite {
+        path : path.display().to_string(),
+        source,
+    })
+}
+
+/// write the image to `dir` as `screenshot-<date>-<time>.png`, returns the
+/// file name
+pub fn save_screenshot(image : &RgbaImage, dir : &Path) -> Result<PathBuf, ResourceError> {
+    std::fs::create_dir_all(dir).map_err(|source| ResourceError::Write { path : dir.display().to_string(), source })?;
+    let path = dir.join(format!("screenshot-{}.png", timestamp(SystemTime::now())));
+    save_png(image, &path)?;
+    Ok(path)
+}
+
+/// UTC time as `YYYYMMDD-HHMMSS-mmm`, sortable and safe in file names
+fn timestamp(time : SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let seconds = since_epoch.as_secs();
+    let (year, month, day) = civil_from_days((seconds / 86_400) as i64);
+    let seconds_of_day = seconds % 86_400;
+    format!("{:04}{:02}{:02}-{:02}{:02}{:02}-{:03}",
+        year, month, day,
+        seconds_of_day / 3600, seconds_of_day / 60 % 60, seconds_of_day % 60,
+        since_epoch.subsec_millis())
+}
+
+/// year, month and day of a number of days since 1970-01-01, after Howard
+/// Hinnant's `civil_from_days`
+fn civil_from_days(days : i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month_index + 2) / 5 + 1) as u32;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 } as u32;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+/// Writes every n-th frame to `frame_000000.png`, `frame_000001.png`, ...
+/// so a video can be made of them, e.g. with
+/// `ffmpeg -framerate 60 -i frame_%06d.png video.mp4`.
+///
+/// Encoding a PNG takes longer than a frame, so the images are written by a
+/// thread of their own.
+pub struct FrameCapture {
+    dir : PathBuf,
+    every : u32,
+    frame : u64,
+    written : u64,
+    sender : Option<mpsc::SyncSender<(PathBuf, RgbaImage)>>,
+    writer : Option<JoinHandle<()>>,
+}
+
+impl FrameCapture {
+    /// capture one of every `every` frames into `dir`
+    pub fn new(dir : &Path, every : u32) -> Result<Self, ResourceError> {
+        std::fs::create_dir_all(dir).map_err(|source| ResourceError::Write { path : dir.display().to_string(), source })?;
+        let (sender, receiver) = mpsc::sync_channel::<(PathBuf, RgbaImage)>(CAPTURE_QUEUE);
+        let writer = std::thread::spawn(move || {
+            for (path, image) in receiver {
+                if let Err(err) = save_png(&image, &path) {
+                    eprintln!("Error: {:#}", anyhow::Error::new(err));
+                }
+            }
+        });
+        Ok(Self {
+            dir : dir.into(),
+            every : every.max(1),
+            frame : 0,
+            written : 0,
+            sender : Some(sender),
+            writer : Some(writer),
+        })
+    }
+
+    /// a frame was rendered, `capture` reads it back when it is one to keep
+    pub fn frame(&mut self, capture : impl FnOnce() -> RgbaImage) {
+        let keep = self.frame % self.every as u64 == 0;
+        self.frame += 1;
+        if !keep {
+            return;
+        }
+        let path = self.dir.join(format!("frame_{:06}.png", self.written));
+        if let Some(sender) = &self.sender {
+            if sender.send((path, capture())).is_ok() {
+                self.written += 1;
+            } else {
+                // the writer is gone, later frames could not be written either
+                eprintln!("Error: the frame writer stopped, capturing ends after {} frames", self.written);
+                self.sender = None;
+            }
+        }
+    }
+
+    /// number of images handed to the writer so far
+    pub fn written(&self) -> u64 {
+        self.written
+    }
+}
+
+impl Drop for FrameCapture {
+    fn drop(&mut self) {
+        // closing the channel lets the writer finish the images it has
+        self.sender.take();
+        if let Some(writer) = self.writer.take() {
+            let _ = writer.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use crate::test_util::TempDir;
+
+    #[test]
+    fn days_since_the_epoch_give_the_date() {
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(civil_from_days(-1), (1969, 12, 31));
+        assert_eq!(civil_from_days(11_016), (2000, 2, 29));
+        assert_eq!(civil_from_days(11_017), (2000, 3, 1));
+    }
+
+    #[test]
+    fn timestamp_has_date_time_and_milliseconds() {
+        assert_eq!(timestamp(UNIX_EPOCH), "19700101-000000-000");
+        let leap_day = UNIX_EPOCH + Duration::from_millis(11_016 * 86_400_000 + 45_296_789);
+        assert_eq!(timestamp(leap_day), "20000229-123456-789");
+    }
+
+    #[test]
+    fn every_nth_frame_is_written() {
+        let dir = TempDir::new("capture");
+        let mut capture = FrameCapture::new(dir.path(), 2).unwrap();
+        let mut captured = 0;
+        for _ in 0..5 {
+            capture.frame(|| {
+                captured += 1;
+                RgbaImage::new(2, 2)
+            });
+        }
+        assert_eq!(captured, 3);
+        assert_eq!(capture.written(), 3);
+        drop(capture);
+
+        let written = (0..4).map(|index| dir.path().join(format!("frame_{:06}.png", index)).exists()).collect::<Vec<_>>();
+        assert_eq!(written, [true, true, true, false]);
+    }
+}
@@ -0,0 +1,60 @@
+#version 330 core
+in vec2 TexCoords;
+out vec4 color;
+
+uniform sampler2D scene;
+// distance to the neighbouring texels, 1 / framebuffer size
+uniform vec2 offset;
+
+uniform bool chaos;
+uniform bool confuse;
+uniform bool shake;
+
+const int edge_kernel[9] = int[](
+    -1, -1, -1,
+    -1,  8, -1,
+    -1, -1, -1
+);
+
+const float blur_kernel[9] = float[](
+    1.0 / 16.0, 2.0 / 16.0, 1.0 / 16.0,
+    2.0 / 16.0, 4.0 / 16.0, 2.0 / 16.0,
+    1.0 / 16.0, 2.0 / 16.0, 1.0 / 16.0
+);
+
+void main()
+{
+    vec3 samples[9];
+    // sample the 3x3 block of texels around this one
+    if (chaos || shake)
+    {
+        for (int i = 0; i < 9; i++)
+        {
+            vec2 neighbour = vec2(float(i % 3 - 1), float(1 - i / 3)) * offset;
+            samples[i] = vec3(texture(scene, TexCoords + neighbour));
+        }
+    }
+
+    color = vec4(0.0, 0.0, 0.0, 1.0);
+    if (chaos)
+    {
+        // edge detection
+        for (int i = 0; i < 9; i++)
+            color.rgb += samples[i] * float(edge_kernel[i]);
+    }
+    else if (confuse)
+    {
+        // inverted colours
+        color = vec4(1.0 - texture(scene, TexCoords).rgb, 1.0);
+    }
+    else if (shake)
+    {
+        // blurred
+        for (int i = 0; i < 9; i++)
+            color.rgb += samples[i] * blur_kernel[i];
+    }
+    else
+    {
+        color = texture(scene, TexCoords);
+    }
+}
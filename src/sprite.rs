@@ -0,0 +1,60 @@
+/// Handle to an image used by the simulation, the renderer maps it to the
+/// actual texture, so game objects never hold any GL resources
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SpriteId(usize);
+
+impl SpriteId {
+    pub fn index(&self) -> usize {
+        self.0
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SpriteEntry {
+    pub name : String,
+    pub path : String,
+}
+
+/// Registry of all sprites the game refers to, with the image file for each
+#[derive(Debug, Default)]
+pub struct Sprites {
+    entries : Vec<SpriteEntry>,
+}
+
+impl Sprites {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// add a sprite, registering the same name twice returns the same handle
+    pub fn register(&mut self, name : &str, path : &str) -> SpriteId {
+        if let Some(id) = self.find(name) {
+            return id;
+        }
+        self.entries.push(SpriteEntry {
+            name : name.into(),
+            path : path.into(),
+        });
+        SpriteId(self.entries.len() - 1)
+    }
+
+    pub fn find(&self, name : &str) -> Option<SpriteId> {
+        self.entries.iter().position(|entry| entry.name == name).map(SpriteId)
+    }
+
+    pub fn get(&self, id : SpriteId) -> &SpriteEntry {
+        &self.entries[id.0]
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (SpriteId, &SpriteEntry)> {
+        self.entries.iter().enumerate().map(|(index, entry)| (SpriteId(index), entry))
+    }
+}
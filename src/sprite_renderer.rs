@@ -1,91 +1,232 @@
-use glow::*;
-use std::ops::Drop;
-use std::rc::Rc;
-
-use crate::shader::Shader;
-use crate::texture::Texture;
-
-pub struct SpriteRenderer {
-    gl : Rc<glow::Context>,
-    vao: glow::VertexArray,
-    vbo: glow::Buffer,
-    shader: Rc<Shader>, 
-}
-
-impl SpriteRenderer {
-    /// constructor, expects a filepath to a 3D model.
-    pub fn new(  gl : Rc<Context>, shader : Rc<Shader> ) -> Self {
-
-        unsafe {
-            let vao  = gl.create_vertex_array().expect("Create VAO");
-            let vbo = gl.create_buffer().expect("Create VBO");
-
-            gl.bind_vertex_array( Some(vao) );
-
-            let positions : [f32;24] = [
-                // pos      // tex
-                0.0, 1.0, 0.0, 1.0,
-                1.0, 0.0, 1.0, 0.0,
-                0.0, 0.0, 0.0, 0.0, 
-            
-                0.0, 1.0, 0.0, 1.0,
-                1.0, 1.0, 1.0, 1.0,
-                1.0, 0.0, 1.0, 0.0
-            ];
-
-            // upload data
-            gl.bind_buffer(glow::ARRAY_BUFFER, Some(vbo) );
-            let u8_buffer = bytemuck::cast_slice(&positions);
-            gl.buffer_data_u8_slice(glow::ARRAY_BUFFER, u8_buffer, glow::STATIC_DRAW);
-
-            // set position/vertex attribute (0) as vec4
-            gl.vertex_attrib_pointer_f32(
-                0,
-                4,
-                glow::FLOAT,
-                false,
-                std::mem::size_of::<f32>() as i32 * 4,
-                std::mem::size_of::<f32>() as i32 * 0);
-            gl.enable_vertex_attrib_array(0);
-
-            Self {
-                gl,
-                vao,
-                vbo,
-                shader,
-            }
-        }
-    }
-    pub fn draw(&self, texture: &Texture,  position: glm::Vec2, size: glm::Vec2, rotate : f32, color:glm::Vec3) {
-        self.shader.use_program();
-        let mut model = glm::translate(&glm::Mat4::identity(), &glm::vec3(position.x,position.y,0.0f32) );
-
-        // unsafe {
-        //     self.gl.polygon_mode(glow::FRONT_AND_BACK, glow::LINE);
-        // }
-        model = glm::translate(&model, &glm::vec3(0.5f32 * size.x, 0.5f32 * size.y, 0.0f32)); 
-        model = glm::rotate(&model, rotate.to_radians(), &glm::vec3(0.0f32, 0.0f32, 1.0f32)); 
-        model = glm::translate(&model, &glm::vec3(-0.5f32 * size.x, -0.5f32 * size.y, 0.0f32));
-        model = glm::scale(&model, &glm::vec3(size.x, size.y, 1.0f32)); 
-  
-        self.shader.set_uniform_mat4("model", &model);
-        self.shader.set_uniform_vec3("spriteColor", &color);
-  
-        unsafe {
-            self.gl.active_texture(glow::TEXTURE0);
-            texture.bind();
-            self.gl.bind_vertex_array( Some(self.vao));
-            self.gl.draw_arrays(glow::TRIANGLES, 0, 6);
-            self.gl.bind_vertex_array( None);
-        }
-    }
-}
-
-impl Drop for SpriteRenderer {
-    fn drop(&mut self) {
-        unsafe {
-            self.gl.delete_vertex_array(self.vao);
-            self.gl.delete_buffer(self.vbo);
-        }
-    }
-}
+use glow::*;
+use std::cell::{Cell, RefCell};
+use std::ops::Drop;
+use std::rc::Rc;
+
+use crate::shader::Shader;
+use crate::texture::Texture;
+use crate::texture_atlas::TextureRegion;
+
+// floats per vertex: <vec2 position, vec2 texCoords, vec4 color>
+const VERTEX_SIZE : usize = 8;
+const VERTICES_PER_QUAD : usize = 6;
+
+// corners of the unit quad, as two triangles
+const QUAD_CORNERS : [(f32, f32); VERTICES_PER_QUAD] = [
+    (0.0, 1.0),
+    (1.0, 0.0),
+    (0.0, 0.0),
+
+    (0.0, 1.0),
+    (1.0, 1.0),
+    (1.0, 0.0),
+];
+
+/// A single sprite submitted to the batch
+#[derive(Debug, Clone, Copy)]
+pub struct SpriteQuad {
+    pub position : glm::Vec2,
+    pub size : glm::Vec2,
+    /// rotation in degrees around the centre of the sprite
+    pub rotate : f32,
+    pub color : glm::Vec4,
+    /// texture coordinates as (u0, v0, u1, v1)
+    pub uv : glm::Vec4,
+}
+
+impl SpriteQuad {
+    pub fn new(position : glm::Vec2, size : glm::Vec2, rotate : f32, color : glm::Vec3) -> Self {
+        Self {
+            position,
+            size,
+            rotate,
+            color : glm::vec4(color.x, color.y, color.z, 1.0),
+            uv : glm::vec4(0.0, 0.0, 1.0, 1.0),
+        }
+    }
+}
+
+struct Submission {
+    layer : i32,
+    texture : glow::Texture,
+    quad : SpriteQuad,
+}
+
+/// Sprite renderer that collects sprites between `begin` and `flush` and
+/// draws them from a single streaming vertex buffer.
+///
+/// Submissions are sorted on layer first and texture second, so sprites
+/// that have to be drawn over others need a higher layer, within a layer
+/// the draw order of different textures is not defined.
+pub struct SpriteRenderer {
+    gl : Rc<glow::Context>,
+    vao: glow::VertexArray,
+    vbo: glow::Buffer,
+    shader: Rc<Shader>,
+    submissions : RefCell<Vec<Submission>>,
+    vertices : RefCell<Vec<f32>>,
+    batching : Cell<bool>,
+    layer : Cell<i32>,
+    draw_calls : Cell<u32>,
+}
+
+impl SpriteRenderer {
+    pub fn new(  gl : Rc<Context>, shader : Rc<Shader> ) -> Self {
+
+        unsafe {
+            let vao  = gl.create_vertex_array().expect("Create VAO");
+            let vbo = gl.create_buffer().expect("Create VBO");
+
+            gl.bind_vertex_array( Some(vao) );
+            gl.bind_buffer(glow::ARRAY_BUFFER, Some(vbo) );
+
+            let stride = (std::mem::size_of::<f32>() * VERTEX_SIZE) as i32;
+            let float_size = std::mem::size_of::<f32>() as i32;
+
+            // position attribute (0) as vec2
+            gl.vertex_attrib_pointer_f32(0, 2, glow::FLOAT, false, stride, 0);
+            gl.enable_vertex_attrib_array(0);
+
+            // texture coordinate attribute (1) as vec2
+            gl.vertex_attrib_pointer_f32(1, 2, glow::FLOAT, false, stride, float_size * 2);
+            gl.enable_vertex_attrib_array(1);
+
+            // color attribute (2) as vec4
+            gl.vertex_attrib_pointer_f32(2, 4, glow::FLOAT, false, stride, float_size * 4);
+            gl.enable_vertex_attrib_array(2);
+
+            gl.bind_vertex_array( None );
+
+            Self {
+                gl,
+                vao,
+                vbo,
+                shader,
+                submissions : RefCell::new(Vec::new()),
+                vertices : RefCell::new(Vec::new()),
+                batching : Cell::new(false),
+                layer : Cell::new(0),
+                draw_calls : Cell::new(0),
+            }
+        }
+    }
+
+    /// start collecting sprites, nothing is drawn until `flush`
+    pub fn begin(&self) {
+        self.submissions.borrow_mut().clear();
+        self.batching.set(true);
+        self.layer.set(0);
+    }
+
+    /// layer for the sprites submitted after this call, higher layers are
+    /// drawn on top of lower ones
+    pub fn set_layer(&self, layer : i32) {
+        self.layer.set(layer);
+    }
+
+    /// add a sprite to the current batch
+    pub fn submit(&self, texture : &Texture, quad : &SpriteQuad) {
+        self.submissions.borrow_mut().push(Submission {
+            layer : self.layer.get(),
+            texture : texture.raw(),
+            quad : *quad,
+        });
+        if !self.batching.get() {
+            self.flush();
+        }
+    }
+
+    /// draw all submitted sprites, with one draw call per texture per layer
+    pub fn flush(&self) {
+        let mut submissions = self.submissions.borrow_mut();
+        self.batching.set(false);
+        self.draw_calls.set(0);
+        if submissions.is_empty() {
+            return;
+        }
+
+        // stable sort, so sprites keep their submit order within a texture
+        submissions.sort_by_key(|submission| (submission.layer, submission.texture));
+
+        let mut vertices = self.vertices.borrow_mut();
+        vertices.clear();
+        for submission in submissions.iter() {
+            Self::push_quad(&mut vertices, &submission.quad);
+        }
+
+        self.shader.use_program();
+        unsafe {
+            self.gl.bind_vertex_array( Some(self.vao));
+            self.gl.bind_buffer(glow::ARRAY_BUFFER, Some(self.vbo));
+            // re-specify the whole buffer, so the driver can orphan the old one
+            self.gl.buffer_data_u8_slice(glow::ARRAY_BUFFER, bytemuck::cast_slice(&vertices), glow::STREAM_DRAW);
+            self.gl.active_texture(glow::TEXTURE0);
+
+            let mut first = 0;
+            while first < submissions.len() {
+                let key = (submissions[first].layer, submissions[first].texture);
+                let count = submissions[first..].iter()
+                    .take_while(|submission| (submission.layer, submission.texture) == key)
+                    .count();
+
+                self.gl.bind_texture(glow::TEXTURE_2D, Some(key.1));
+                self.gl.draw_arrays(
+                    glow::TRIANGLES,
+                    (first * VERTICES_PER_QUAD) as i32,
+                    (count * VERTICES_PER_QUAD) as i32);
+                self.draw_calls.set(self.draw_calls.get() + 1);
+                first += count;
+            }
+            self.gl.bind_vertex_array( None);
+        }
+        submissions.clear();
+    }
+
+    /// number of draw calls issued by the last flush
+    pub fn draw_calls(&self) -> u32 {
+        self.draw_calls.get()
+    }
+
+    /// transform the unit quad on the cpu and append its vertices
+    fn push_quad(vertices : &mut Vec<f32>, quad : &SpriteQuad) {
+        let center = quad.position + quad.size.scale(0.5);
+        let (sin, cos) = quad.rotate.to_radians().sin_cos();
+
+        for (x, y) in QUAD_CORNERS.iter() {
+            let local = glm::vec2((x - 0.5) * quad.size.x, (y - 0.5) * quad.size.y);
+            let rotated = glm::vec2(
+                local.x * cos - local.y * sin,
+                local.x * sin + local.y * cos);
+            let u = quad.uv.x + (quad.uv.z - quad.uv.x) * x;
+            let v = quad.uv.y + (quad.uv.w - quad.uv.y) * y;
+
+            vertices.extend_from_slice(&[
+                center.x + rotated.x, center.y + rotated.y,
+                u, v,
+                quad.color.x, quad.color.y, quad.color.z, quad.color.w,
+            ]);
+        }
+    }
+
+    /// draw a single sprite, inside `begin` / `flush` it is added to the
+    /// batch, otherwise it is drawn right away
+    pub fn draw(&self, texture: &Texture,  position: glm::Vec2, size: glm::Vec2, rotate : f32, color:glm::Vec3) {
+        self.submit(texture, &SpriteQuad::new(position, size, rotate, color));
+    }
+
+    /// draw a sprite from part of a texture, e.g. an atlas region
+    pub fn draw_region(&self, region: &TextureRegion,  position: glm::Vec2, size: glm::Vec2, rotate : f32, color:glm::Vec3) {
+        let mut quad = SpriteQuad::new(position, size, rotate, color);
+        quad.uv = region.uv();
+        self.submit(region.texture(), &quad);
+    }
+}
+
+impl Drop for SpriteRenderer {
+    fn drop(&mut self) {
+        unsafe {
+            self.gl.delete_vertex_array(self.vao);
+            self.gl.delete_buffer(self.vbo);
+        }
+    }
+}
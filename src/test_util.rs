@@ -0,0 +1,25 @@
+use std::path::{Path, PathBuf};
+
+/// empty directory for one test, removed when dropped
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    /// `name` keeps the directories of the tests apart, the process id
+    /// those of test runs at the same time
+    pub fn new(name : &str) -> Self {
+        let dir = std::env::temp_dir().join(format!("rust_breakout_{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        Self(dir)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
@@ -0,0 +1,185 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+use image::RgbaImage;
+
+use crate::error::ResourceError;
+use crate::sprite_renderer::{SpriteQuad, SpriteRenderer};
+use crate::texture::Texture;
+use crate::texture_atlas::{TextureAtlas, TextureRegion};
+
+// characters available in every font
+const FIRST_CHAR : char = ' ';
+const LAST_CHAR : char = '~';
+// drawn for characters the font does not have
+const REPLACEMENT_CHAR : char = '?';
+
+/// Placement of one character, in pixels at scale 1
+#[derive(Debug, Clone)]
+pub struct Glyph {
+    /// part of the font texture, `None` for blank characters
+    region : Option<TextureRegion>,
+    size : glm::Vec2,
+    /// from the pen position on top of the line to the top left of the glyph
+    offset : glm::Vec2,
+    /// how far the pen moves after this character
+    advance : f32,
+}
+
+/// Glyphs of a font packed into one texture
+pub struct Font {
+    atlas : TextureAtlas,
+    glyphs : HashMap<char, Glyph>,
+    line_height : f32,
+}
+
+impl Font {
+    /// rasterise the printable ASCII characters of a TrueType font at
+    /// `pixel_size` pixels
+    pub fn from_truetype(gl : Rc<glow::Context>, name : &str, data : &[u8], pixel_size : f32) -> Result<Self, ResourceError> {
+        let font = fontdue::Font::from_bytes(data, fontdue::FontSettings::default())
+            .map_err(|message| ResourceError::Font { name : name.into(), message : message.into() })?;
+        let line_metrics = font.horizontal_line_metrics(pixel_size)
+            .ok_or_else(|| ResourceError::Font { name : name.into(), message : "no horizontal metrics".into() })?;
+
+        let mut images = Vec::new();
+        let mut glyphs = HashMap::new();
+        for c in FIRST_CHAR..=LAST_CHAR {
+            let (metrics, coverage) = font.rasterize(c, pixel_size);
+            if metrics.width > 0 && metrics.height > 0 {
+                let mut image = RgbaImage::new(metrics.width as u32, metrics.height as u32);
+                for (pixel, alpha) in image.pixels_mut().zip(coverage) {
+                    *pixel = image::Rgba([255, 255, 255, alpha]);
+                }
+                images.push((c.to_string(), image));
+            }
+
+            // fontdue measures from the baseline upwards
+            let top = line_metrics.ascent - (metrics.ymin as f32 + metrics.height as f32);
+            glyphs.insert(c, Glyph {
+                region : None,
+                size : glm::vec2(metrics.width as f32, metrics.height as f32),
+                offset : glm::vec2(metrics.xmin as f32, top),
+                advance : metrics.advance_width,
+            });
+        }
+
+        Self::with_atlas(gl, name, images, glyphs, line_metrics.new_line_size)
+    }
+
+    /// cut a monospaced font from an image with a grid of `cell_width` by
+    /// `cell_height` cells, 16 per row, holding the characters from ' ' on
+    pub fn from_bitmap(gl : Rc<glow::Context>, name : &str, image : &RgbaImage, cell_width : u32, cell_height : u32) -> Result<Self, ResourceError> {
+        let columns = image.width() / cell_width.max(1);
+        if columns == 0 {
+            return Err(ResourceError::Font { name : name.into(), message : "image is smaller than one cell".into() });
+        }
+
+        let mut images = Vec::new();
+        let mut glyphs = HashMap::new();
+        for (index, c) in (FIRST_CHAR..=LAST_CHAR).enumerate() {
+            let (x, y) = ((index as u32 % columns) * cell_width, (index as u32 / columns) * cell_height);
+            if y + cell_height > image.height() {
+                break;
+            }
+            let cell = image::imageops::crop_imm(image, x, y, cell_width, cell_height).to_image();
+            images.push((c.to_string(), cell));
+            glyphs.insert(c, Glyph {
+                region : None,
+                size : glm::vec2(cell_width as f32, cell_height as f32),
+                offset : glm::vec2(0.0, 0.0),
+                advance : cell_width as f32,
+            });
+        }
+
+        Self::with_atlas(gl, name, images, glyphs, cell_height as f32)
+    }
+
+    fn with_atlas(gl : Rc<glow::Context>, name : &str, images : Vec<(String, RgbaImage)>, mut glyphs : HashMap<char, Glyph>, line_height : f32) -> Result<Self, ResourceError> {
+        let atlas = TextureAtlas::new(gl, name, images)?;
+        for (c, glyph) in glyphs.iter_mut() {
+            glyph.region = atlas.region(&c.to_string());
+        }
+        Ok(Self {
+            atlas,
+            glyphs,
+            line_height,
+        })
+    }
+
+    pub fn texture(&self) -> Rc<Texture> {
+        self.atlas.texture()
+    }
+
+    /// distance between two lines at scale 1
+    pub fn line_height(&self) -> f32 {
+        self.line_height
+    }
+
+    fn glyph(&self, c : char) -> Option<&Glyph> {
+        self.glyphs.get(&c).or_else(|| self.glyphs.get(&REPLACEMENT_CHAR))
+    }
+}
+
+/// Draws strings with a `Font` through the sprite renderer, so text uses
+/// the same orthographic projection as the sprites
+pub struct TextRenderer {
+    font : Rc<Font>,
+}
+
+impl TextRenderer {
+    pub fn new(font : Rc<Font>) -> Self {
+        Self {
+            font,
+        }
+    }
+
+    pub fn font(&self) -> &Font {
+        &self.font
+    }
+
+    /// width and height of the text, lines are separated by '\n'
+    pub fn measure(&self, text : &str, scale : f32) -> glm::Vec2 {
+        let width = text.lines()
+            .map(|line| line.chars().filter_map(|c| self.font.glyph(c)).map(|glyph| glyph.advance).sum::<f32>())
+            .fold(0.0, f32::max);
+        let lines = text.lines().count().max(1);
+        glm::vec2(width, lines as f32 * self.font.line_height()).scale(scale)
+    }
+
+    /// quads for the text with its top left corner at `position`
+    pub fn layout(&self, text : &str, position : glm::Vec2, scale : f32, color : glm::Vec3) -> Vec<SpriteQuad> {
+        let mut quads = Vec::new();
+        let mut pen = position;
+        for c in text.chars() {
+            if c == '\n' {
+                pen = glm::vec2(position.x, pen.y + self.font.line_height() * scale);
+                continue;
+            }
+            let glyph = match self.font.glyph(c) {
+                Some(glyph) => glyph,
+                None => continue,
+            };
+            if let Some(region) = &glyph.region {
+                let mut quad = SpriteQuad::new(pen + glyph.offset.scale(scale), glyph.size.scale(scale), 0.0, color);
+                quad.uv = region.uv();
+                quads.push(quad);
+            }
+            pen.x += glyph.advance * scale;
+        }
+        quads
+    }
+
+    /// draw the text with its top left corner at `position`
+    pub fn draw(&self, sprite_renderer : &SpriteRenderer, text : &str, position : glm::Vec2, scale : f32, color : glm::Vec3) {
+        let texture = self.font.texture();
+        for quad in self.layout(text, position, scale, color) {
+            sprite_renderer.submit(&texture, &quad);
+        }
+    }
+
+    /// draw the text centred horizontally on `center_x`
+    pub fn draw_centered(&self, sprite_renderer : &SpriteRenderer, text : &str, center_x : f32, y : f32, scale : f32, color : glm::Vec3) {
+        let width = self.measure(text, scale).x;
+        self.draw(sprite_renderer, text, glm::vec2(center_x - width / 2.0, y), scale, color);
+    }
+}
@@ -0,0 +1,288 @@
+use std::path::Path;
+use std::cell::RefCell;
+use std::rc::Rc;
+use image::RgbaImage;
+
+use crate::error::ResourceError;
+use crate::texture::Texture;
+
+// free pixels around every image, filled with its edge pixels so linear
+// filtering never samples a neighbouring image
+const PADDING : u32 = 2;
+// largest atlas that will be created
+const MAX_ATLAS_SIZE : u32 = 4096;
+
+/// Part of a texture that can be drawn as a sprite
+#[derive(Debug, Clone)]
+pub struct TextureRegion {
+    texture : Rc<Texture>,
+    uv : glm::Vec4,
+}
+
+impl TextureRegion {
+    /// region covering the whole texture
+    pub fn whole(texture : Rc<Texture>) -> Self {
+        Self {
+            texture,
+            uv : glm::vec4(0.0, 0.0, 1.0, 1.0),
+        }
+    }
+
+    pub fn texture(&self) -> &Texture {
+        self.texture.as_ref()
+    }
+
+    /// texture coordinates as (u0, v0, u1, v1)
+    pub fn uv(&self) -> glm::Vec4 {
+        self.uv
+    }
+}
+
+/// Placement of one image in the atlas
+#[derive(Debug, Clone)]
+pub struct AtlasEntry {
+    pub name : String,
+    pub x : u32,
+    pub y : u32,
+    pub width : u32,
+    pub height : u32,
+    pub uv : glm::Vec4,
+}
+
+/// Several images packed into a single texture
+#[derive(Debug)]
+pub struct TextureAtlas {
+    name : String,
+    texture : Rc<Texture>,
+    image : RefCell<RgbaImage>,
+    entries : Vec<AtlasEntry>,
+}
+
+impl TextureAtlas {
+    /// pack the images and upload the result as one texture
+    pub fn new(gl : Rc<glow::Context>, name : &str, images : Vec<(String, RgbaImage)>) -> Result<Self, ResourceError> {
+        let sizes : Vec<(u32, u32)> = images.iter().map(|(_, image)| image.dimensions()).collect();
+        let (width, height, places) = layout(name, &sizes)?;
+
+        let mut atlas_image = RgbaImage::new(width, height);
+        let mut entries = Vec::with_capacity(images.len());
+        for ((image_name, image), (x, y, uv)) in images.iter().zip(places) {
+            blit_extruded(&mut atlas_image, image, x, y);
+            entries.push(AtlasEntry {
+                name : image_name.clone(),
+                x,
+                y,
+                width : image.width(),
+                height : image.height(),
+                uv,
+            });
+        }
+
+        let texture = Texture::new(gl, width, height, atlas_image.as_raw().clone())
+            .map_err(|message| ResourceError::Atlas { name : name.into(), message })?;
+
+        Ok(Self {
+            name : name.into(),
+            texture : Rc::new(texture),
+            image : RefCell::new(atlas_image),
+            entries,
+        })
+    }
+
+    pub fn texture(&self) -> Rc<Texture> {
+        self.texture.clone()
+    }
+
+    pub fn entries(&self) -> &[AtlasEntry] {
+        &self.entries
+    }
+
+    /// the part of the atlas holding the named image
+    pub fn region(&self, name : &str) -> Option<TextureRegion> {
+        self.entries.iter().find(|entry| entry.name == name).map(|entry| TextureRegion {
+            texture : self.texture.clone(),
+            uv : entry.uv,
+        })
+    }
+
+    /// put a new version of the named image in its place and upload the
+    /// atlas again, the image has to keep its size
+    pub fn replace_image(&self, name : &str, image : &RgbaImage) -> Result<(), ResourceError> {
+        let entry = self.entries.iter().find(|entry| entry.name == name)
+            .ok_or_else(|| ResourceError::MissingTexture(name.into()))?;
+        if image.dimensions() != (entry.width, entry.height) {
+            return Err(ResourceError::Atlas {
+                name : self.name.clone(),
+                message : format!("image '{}' changed size from {}x{} to {}x{}, restart to pack it again",
+                    name, entry.width, entry.height, image.width(), image.height()),
+            });
+        }
+
+        let mut atlas_image = self.image.borrow_mut();
+        blit_extruded(&mut atlas_image, image, entry.x, entry.y);
+        self.texture.update(atlas_image.width(), atlas_image.height(), atlas_image.as_raw());
+        Ok(())
+    }
+
+    /// write the atlas image to `path` and its layout next to it, with the
+    /// extension replaced by `.txt`
+    pub fn dump(&self, path : &Path) -> Result<(), ResourceError> {
+        let image = self.image.borrow();
+        image.save(path).map_err(|source| ResourceError::ImageWrite {
+            path : path.display().to_string(),
+            source,
+        })?;
+
+        let mut layout = format!("# {}x{}\n# name x y width height u0 v0 u1 v1\n", image.width(), image.height());
+        for entry in self.entries.iter() {
+            layout += &format!("{} {} {} {} {} {} {} {} {}\n",
+                entry.name, entry.x, entry.y, entry.width, entry.height,
+                entry.uv.x, entry.uv.y, entry.uv.z, entry.uv.w);
+        }
+        let layout_path = path.with_extension("txt");
+        std::fs::write(&layout_path, layout).map_err(|source| ResourceError::Write {
+            path : layout_path.display().to_string(),
+            source,
+        })
+    }
+}
+
+/// Atlas width, height and the position and texture coordinates of every image
+type Layout = (u32, u32, Vec<(u32, u32, glm::Vec4)>);
+
+/// place images of the given sizes in an atlas, leaving PADDING free pixels
+/// around each of them
+fn layout(name : &str, sizes : &[(u32, u32)]) -> Result<Layout, ResourceError> {
+    if let Some(index) = sizes.iter().position(|(w, h)| *w == 0 || *h == 0) {
+        return Err(ResourceError::Atlas {
+            name : name.into(),
+            message : format!("image {} is empty", index),
+        });
+    }
+
+    let padded : Vec<(u32, u32)> = sizes.iter()
+        .map(|(w, h)| (w + PADDING * 2, h + PADDING * 2))
+        .collect();
+    let (width, height, placements) = pack_shelves(&padded)
+        .ok_or_else(|| ResourceError::Atlas {
+            name : name.into(),
+            message : format!("images do not fit in {}x{}", MAX_ATLAS_SIZE, MAX_ATLAS_SIZE),
+        })?;
+
+    let places = sizes.iter().zip(placements).map(|((w, h), (x, y))| {
+        let (x, y) = (x + PADDING, y + PADDING);
+        let uv = glm::vec4(
+            x as f32 / width as f32,
+            y as f32 / height as f32,
+            (x + w) as f32 / width as f32,
+            (y + h) as f32 / height as f32);
+        (x, y, uv)
+    }).collect();
+    Ok((width, height, places))
+}
+
+/// Atlas width, height and the position of every packed rectangle
+type Packing = (u32, u32, Vec<(u32, u32)>);
+
+/// Shelf packing, tallest rectangles first. Tries square power of two
+/// atlases until everything fits, returns the atlas size and the position
+/// of every rectangle in the order they were given.
+fn pack_shelves(sizes : &[(u32, u32)]) -> Option<Packing> {
+    let mut order : Vec<usize> = (0..sizes.len()).collect();
+    order.sort_by_key(|index| std::cmp::Reverse(sizes[*index].1));
+
+    let area : u32 = sizes.iter().map(|(w, h)| w * h).sum();
+    let widest = sizes.iter().map(|(w, _)| *w).max().unwrap_or(1);
+    let mut size = ((area as f32).sqrt() as u32).max(widest).next_power_of_two();
+
+    while size <= MAX_ATLAS_SIZE {
+        let mut placements = vec![(0, 0); sizes.len()];
+        let (mut x, mut y, mut shelf_height) = (0, 0, 0);
+        let mut fits = true;
+
+        for index in order.iter() {
+            let (w, h) = sizes[*index];
+            if x + w > size {
+                // start a new shelf
+                y += shelf_height;
+                x = 0;
+                shelf_height = 0;
+            }
+            if y + h > size {
+                fits = false;
+                break;
+            }
+            placements[*index] = (x, y);
+            x += w;
+            shelf_height = shelf_height.max(h);
+        }
+
+        if fits {
+            let height = (y + shelf_height).max(1).next_power_of_two();
+            return Some((size, height, placements));
+        }
+        size *= 2;
+    }
+    None
+}
+
+/// copy the image into the atlas and repeat its edge pixels in the padding
+fn blit_extruded(atlas : &mut RgbaImage, image : &RgbaImage, x : u32, y : u32) {
+    let (w, h) = image.dimensions();
+    let pad = PADDING as i64;
+    for dy in -pad..(h as i64 + pad) {
+        for dx in -pad..(w as i64 + pad) {
+            let src_x = dx.clamp(0, w as i64 - 1) as u32;
+            let src_y = dy.clamp(0, h as i64 - 1) as u32;
+            let dst_x = (x as i64 + dx) as u32;
+            let dst_y = (y as i64 + dy) as u32;
+            atlas.put_pixel(dst_x, dst_y, *image.get_pixel(src_x, src_y));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SIZES : [(u32, u32); 6] = [(16, 16), (100, 20), (64, 64), (3, 120), (300, 8), (1, 1)];
+
+    #[test]
+    fn packed_rectangles_do_not_overlap() {
+        let (width, height, placements) = pack_shelves(&SIZES).unwrap();
+        for (i, ((x, y), (w, h))) in placements.iter().zip(SIZES.iter()).enumerate() {
+            assert!(x + w <= width && y + h <= height, "rectangle {} is outside the atlas", i);
+            for (j, ((other_x, other_y), (other_w, other_h))) in placements.iter().zip(SIZES.iter()).enumerate().skip(i + 1) {
+                let apart = x + w <= *other_x || other_x + other_w <= *x
+                    || y + h <= *other_y || other_y + other_h <= *y;
+                assert!(apart, "rectangles {} and {} overlap", i, j);
+            }
+        }
+    }
+
+    #[test]
+    fn too_large_rectangles_do_not_fit() {
+        assert!(pack_shelves(&[(MAX_ATLAS_SIZE + 1, 1)]).is_none());
+    }
+
+    #[test]
+    fn texture_coordinates_cover_the_image_inside_the_atlas() {
+        let (width, height, places) = layout("test", &SIZES).unwrap();
+        for ((x, y, uv), (w, h)) in places.iter().zip(SIZES.iter()) {
+            assert!(*x >= PADDING && *y >= PADDING);
+            assert!(x + w + PADDING <= width && y + h + PADDING <= height);
+            for value in [uv.x, uv.y, uv.z, uv.w] {
+                assert!((0.0..=1.0).contains(&value), "{:?} is outside [0, 1]", uv);
+            }
+            assert_eq!(uv.x * width as f32, *x as f32);
+            assert_eq!((uv.z - uv.x) * width as f32, *w as f32);
+            assert_eq!((uv.w - uv.y) * height as f32, *h as f32);
+        }
+    }
+
+    #[test]
+    fn empty_images_are_rejected() {
+        let error = layout("test", &[(16, 16), (0, 16)]).unwrap_err();
+        assert!(matches!(error, ResourceError::Atlas { .. }), "{:?}", error);
+    }
+}
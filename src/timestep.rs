@@ -0,0 +1,96 @@
+/// Accumulator for running the simulation at a fixed rate, independent of
+/// how often frames are rendered
+#[derive(Debug)]
+pub struct FixedTimestep {
+    step : f32,
+    max_steps : u32,
+    accumulator : f32,
+}
+
+impl FixedTimestep {
+    /// simulation running `steps_per_second` updates per second
+    pub fn new(steps_per_second : f32) -> Self {
+        Self {
+            step : 1.0 / steps_per_second,
+            max_steps : 8,
+            accumulator : 0.0,
+        }
+    }
+
+    /// upper limit of updates per frame, time beyond that is dropped so a
+    /// long stall cannot cause a spiral of death
+    pub fn with_max_steps(mut self, max_steps : u32) -> Self {
+        self.max_steps = max_steps.max(1);
+        self
+    }
+
+    /// duration of a single simulation step in seconds
+    pub fn step(&self) -> f32 {
+        self.step
+    }
+
+    /// add the time that passed since the previous frame, returns the
+    /// number of simulation steps to run for this frame
+    pub fn advance(&mut self, frame_time : f32) -> u32 {
+        let max_time = self.step * self.max_steps as f32;
+        self.accumulator = (self.accumulator + frame_time.max(0.0)).min(max_time);
+
+        let mut steps = 0;
+        while self.accumulator >= self.step {
+            self.accumulator -= self.step;
+            steps += 1;
+        }
+        steps
+    }
+
+    /// fraction of a step left in the accumulator, used to interpolate
+    /// between the previous and the current simulation state
+    pub fn alpha(&self) -> f32 {
+        self.accumulator / self.step
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn whole_steps_run_and_the_rest_is_kept() {
+        // a quarter second step is exact in floating point
+        let mut timestep = FixedTimestep::new(4.0);
+        assert_eq!(timestep.advance(0.625), 2);
+        assert_eq!(timestep.alpha(), 0.5);
+        assert_eq!(timestep.advance(0.125), 1);
+        assert_eq!(timestep.alpha(), 0.0);
+        assert_eq!(timestep.advance(0.125), 0);
+        assert_eq!(timestep.alpha(), 0.5);
+    }
+
+    #[test]
+    fn long_frames_are_capped_and_the_rest_dropped() {
+        let mut timestep = FixedTimestep::new(4.0).with_max_steps(3);
+        assert_eq!(timestep.advance(10.0), 3);
+        assert_eq!(timestep.alpha(), 0.0);
+        assert_eq!(timestep.advance(0.0), 0);
+        // the time that did not fit is gone for good
+        assert_eq!(timestep.advance(0.125), 0);
+    }
+
+    #[test]
+    fn negative_frame_time_is_ignored() {
+        let mut timestep = FixedTimestep::new(4.0);
+        assert_eq!(timestep.advance(-1.0), 0);
+        assert_eq!(timestep.alpha(), 0.0);
+    }
+
+    #[test]
+    fn alpha_stays_below_one() {
+        let mut timestep = FixedTimestep::new(120.0);
+        for frame in 0..1000 {
+            // frame times between 1 and 40 ms
+            timestep.advance((1 + frame * 7 % 40) as f32 / 1000.0);
+            let alpha = timestep.alpha();
+            assert!((0.0..1.0).contains(&alpha), "alpha {} after frame {}", alpha, frame);
+        }
+    }
+}
@@ -0,0 +1,135 @@
+/// Maps the logical playfield onto the window. The playfield keeps its
+/// aspect ratio, scaled as large as fits and centred, with bars above
+/// and below (letterbox) or left and right (pillarbox) of it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Viewport {
+    logical_width : u32,
+    logical_height : u32,
+    physical_width : u32,
+    physical_height : u32,
+}
+
+impl Viewport {
+    /// a window of the same size as the playfield, until `resize` is called
+    pub fn new(logical_width : u32, logical_height : u32) -> Self {
+        Self {
+            logical_width,
+            logical_height,
+            physical_width : logical_width,
+            physical_height : logical_height,
+        }
+    }
+
+    /// the window now has this size in physical pixels
+    pub fn resize(&mut self, physical_width : u32, physical_height : u32) {
+        self.physical_width = physical_width;
+        self.physical_height = physical_height;
+    }
+
+    pub fn logical_size(&self) -> (u32, u32) {
+        (self.logical_width, self.logical_height)
+    }
+
+    pub fn physical_size(&self) -> (u32, u32) {
+        (self.physical_width, self.physical_height)
+    }
+
+    /// physical pixels per logical pixel
+    pub fn scale(&self) -> f32 {
+        let scale_x = self.physical_width as f32 / self.logical_width.max(1) as f32;
+        let scale_y = self.physical_height as f32 / self.logical_height.max(1) as f32;
+        scale_x.min(scale_y)
+    }
+
+    /// the playfield in physical pixels as x, y, width and height, measured
+    /// from the top left of the window
+    pub fn rect(&self) -> (i32, i32, i32, i32) {
+        let scale = self.scale();
+        let width = (self.logical_width as f32 * scale).round() as i32;
+        let height = (self.logical_height as f32 * scale).round() as i32;
+        let x = (self.physical_width as i32 - width) / 2;
+        let y = (self.physical_height as i32 - height) / 2;
+        (x, y, width, height)
+    }
+
+    /// the playfield as arguments for `glViewport`, which counts from the
+    /// bottom left of the window
+    pub fn gl_rect(&self) -> (i32, i32, i32, i32) {
+        let (x, y, width, height) = self.rect();
+        (x, self.physical_height as i32 - y - height, width, height)
+    }
+
+    /// a position in physical window pixels, like the cursor position, in
+    /// logical playfield coordinates. Positions on the bars fall outside
+    /// the playfield.
+    pub fn to_logical(&self, physical : glm::Vec2) -> glm::Vec2 {
+        let (x, y, _, _) = self.rect();
+        let scale = self.scale();
+        if scale <= 0.0 {
+            return glm::vec2(0.0, 0.0);
+        }
+        (physical - glm::vec2(x as f32, y as f32)) / scale
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_aspect_fills_the_window() {
+        let mut viewport = Viewport::new(800, 600);
+        assert_eq!(viewport.rect(), (0, 0, 800, 600));
+        viewport.resize(1600, 1200);
+        assert_eq!(viewport.scale(), 2.0);
+        assert_eq!(viewport.rect(), (0, 0, 1600, 1200));
+    }
+
+    #[test]
+    fn wide_window_has_bars_left_and_right() {
+        let mut viewport = Viewport::new(800, 600);
+        viewport.resize(1920, 1080);
+        assert_eq!(viewport.scale(), 1.8);
+        assert_eq!(viewport.rect(), (240, 0, 1440, 1080));
+        assert_eq!(viewport.gl_rect(), (240, 0, 1440, 1080));
+    }
+
+    #[test]
+    fn tall_window_has_bars_above_and_below() {
+        let mut viewport = Viewport::new(800, 600);
+        viewport.resize(800, 1000);
+        assert_eq!(viewport.scale(), 1.0);
+        assert_eq!(viewport.rect(), (0, 200, 800, 600));
+        // glViewport counts from the bottom, the bars are the same size
+        assert_eq!(viewport.gl_rect(), (0, 200, 800, 600));
+
+        viewport.resize(800, 900);
+        assert_eq!(viewport.rect(), (0, 150, 800, 600));
+        assert_eq!(viewport.gl_rect(), (0, 150, 800, 600));
+    }
+
+    #[test]
+    fn window_positions_map_to_the_playfield() {
+        let mut viewport = Viewport::new(800, 600);
+        viewport.resize(1920, 1080);
+        assert_eq!(viewport.to_logical(glm::vec2(240.0, 0.0)), glm::vec2(0.0, 0.0));
+        assert_eq!(viewport.to_logical(glm::vec2(960.0, 540.0)), glm::vec2(400.0, 300.0));
+        assert_eq!(viewport.to_logical(glm::vec2(1680.0, 1080.0)), glm::vec2(800.0, 600.0));
+        // the bars are outside the playfield
+        assert!(viewport.to_logical(glm::vec2(100.0, 540.0)).x < 0.0);
+        assert!(viewport.to_logical(glm::vec2(1800.0, 540.0)).x > 800.0);
+
+        viewport.resize(800, 1000);
+        assert_eq!(viewport.to_logical(glm::vec2(400.0, 500.0)), glm::vec2(400.0, 300.0));
+        assert!(viewport.to_logical(glm::vec2(400.0, 100.0)).y < 0.0);
+        assert!(viewport.to_logical(glm::vec2(400.0, 900.0)).y > 600.0);
+    }
+
+    #[test]
+    fn minimised_window_maps_to_the_origin() {
+        let mut viewport = Viewport::new(800, 600);
+        viewport.resize(0, 0);
+        assert_eq!(viewport.rect(), (0, 0, 0, 0));
+        assert_eq!(viewport.to_logical(glm::vec2(10.0, 10.0)), glm::vec2(0.0, 0.0));
+    }
+}